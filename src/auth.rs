@@ -57,6 +57,30 @@ pub fn authenticate_login(username: &str, password: &str) -> Result<(), String>
     }
 }
 
+/// Fetch the secret `name` held for account `user` from RookGuard, for a
+/// service's `credentials`. rev is root, so rookd releases any account's
+/// secrets to it. The caller owns the bytes and must not log them. Blocking,
+/// like [`authenticate_login`].
+pub fn fetch_secret(user: &str, name: &str) -> Result<Vec<u8>, String> {
+    let sock = rookd_sock();
+    let mut stream =
+        UnixStream::connect(&sock).map_err(|e| format!("connect rookd {}: {e}", sock.display()))?;
+    let req = Request::GetSecret {
+        user: user.to_string(),
+        name: name.to_string(),
+    };
+    ipc::write_msg(&mut stream, &req).map_err(|e| format!("rookd write: {e}"))?;
+    match ipc::read_msg::<Response, _>(&mut stream).map_err(|e| format!("rookd read: {e}"))? {
+        Response::Secret { value } => Ok(value),
+        Response::Denied { reason } => Err(format!("secret '{name}' denied: {reason}")),
+        Response::Locked { retry_after_secs } => Err(format!(
+            "secret '{name}' locked; retry in {retry_after_secs}s"
+        )),
+        // Never format the reply: it may carry secret material.
+        _ => Err(format!("unexpected rookd reply for secret '{name}'")),
+    }
+}
+
 /// Resolve a login account name to its real uid/gid/home/shell through UAC, so
 /// the StartSessionAuth path never trusts a caller-supplied identity.
 pub fn resolve_login(username: &str) -> Result<Target, String> {
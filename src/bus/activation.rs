@@ -10,10 +10,14 @@
 //! Activation is only attempted for names some loaded service actually declares,
 //! so a client cannot make rev start anything that did not opt in. Concurrent
 //! lookups for the same name start the service only once (`IN_FLIGHT`).
+//!
+//! A service that `requires-bus-name` is held before its fork until those names
+//! are on the Highway ([`require`]), activating their providers the same way.
 
 use std::collections::HashSet;
-use std::sync::Mutex;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use once_cell::sync::Lazy;
 
@@ -23,8 +27,10 @@ use super::registry::Registry;
 /// trigger a single service start rather than one per caller.
 static IN_FLIGHT: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
 
-/// How long to wait for an activated service to register its name.
-const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(5);
+/// The Highway's registry, for service starts that wait on a bus name. Set when
+/// the Highway starts.
+static HIGHWAY: OnceLock<Arc<Registry>> = OnceLock::new();
+
 /// How often to re-check the registry while waiting.
 const POLL_INTERVAL: Duration = Duration::from_millis(25);
 
@@ -36,6 +42,11 @@ fn provider_of(bus_name: &str) -> Option<String> {
         .map(|(name, _)| name)
 }
 
+/// Make the Highway's registry the one [`require`] waits on.
+pub fn set_highway(registry: Arc<Registry>) {
+    let _ = HIGHWAY.set(registry);
+}
+
 /// Ensure the service providing `bus_name` is started and has registered the
 /// name on the bus. Returns whether `bus_name` is registered by the time this
 /// returns: `true` if it was already registered or activation succeeded,
@@ -56,12 +67,15 @@ pub async fn activate(bus_name: &str, registry: &Registry) -> bool {
             "rev",
             &format!("bus-activation: starting '{service}' to provide '{bus_name}'"),
         );
-        crate::service::start_known_service(&service);
+        // Off the async runtime: the start may itself wait on bus names.
+        tokio::task::spawn_blocking(move || crate::service::start_known_service(&service));
     }
 
+    // How long to wait for the service to register (`activation-timeout-sec`).
+    let timeout = Duration::from_secs(crate::settings::get().activation_timeout_sec);
     let mut waited = Duration::ZERO;
     let mut found = registry.lookup(bus_name).is_some();
-    while !found && waited < ACTIVATION_TIMEOUT {
+    while !found && waited < timeout {
         tokio::time::sleep(POLL_INTERVAL).await;
         waited += POLL_INTERVAL;
         found = registry.lookup(bus_name).is_some();
@@ -73,6 +87,51 @@ pub async fn activate(bus_name: &str, registry: &Registry) -> bool {
     found
 }
 
+/// Block until every name in `names` is registered on the Highway, starting
+/// the services that provide the missing ones. Fails with the names still
+/// missing once `timeout` has passed. Before the Highway is up nothing is
+/// registered, so the wait simply continues.
+pub fn require(names: &[String], timeout: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut started = HashSet::new();
+    loop {
+        let missing: Vec<&str> = names
+            .iter()
+            .map(String::as_str)
+            .filter(|n| HIGHWAY.get().is_none_or(|r| r.lookup(n).is_none()))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "bus names not registered within {}s: {}",
+                timeout.as_secs(),
+                missing.join(", ")
+            ));
+        }
+        for bus_name in missing {
+            if !started.insert(bus_name.to_string()) {
+                continue;
+            }
+            let Some(service) = provider_of(bus_name) else {
+                continue;
+            };
+            if IN_FLIGHT.lock().unwrap().insert(bus_name.to_string()) {
+                crate::logger::write_log(
+                    "rev",
+                    &format!(
+                        "bus-activation: starting '{service}' to provide required '{bus_name}'"
+                    ),
+                );
+                crate::service::start_known_service(&service);
+                IN_FLIGHT.lock().unwrap().remove(bus_name);
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,6 +147,17 @@ mod tests {
         assert!(!got);
     }
 
+    #[test]
+    fn required_names_time_out() {
+        assert!(require(&[], Duration::ZERO).is_ok());
+        let err = require(
+            &["nobody.registers.this".to_string()],
+            Duration::from_millis(50),
+        )
+        .unwrap_err();
+        assert!(err.contains("nobody.registers.this"), "{err}");
+    }
+
     #[test]
     fn already_registered_short_circuits() {
         // If the name is already on the bus, activate returns true without
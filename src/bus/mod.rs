@@ -31,6 +31,7 @@ pub mod policy;
 pub mod protocol;
 pub mod registry;
 pub mod server;
+pub mod signals;
 
 use std::path::PathBuf;
 
@@ -3,12 +3,15 @@
 //! The protocol types and frame I/O now live in the standalone `wirebus-proto`
 //! crate, so peers (e.g. RookGuard) can speak WireBus without depending on rev.
 //! This module re-exports them so existing `crate::bus::protocol::*` paths keep
-//! working, and adds the rev-side conversion from the internal `ServiceInfo`
-//! runtime state into the neutral wire `ServiceSnapshot`.
+//! working, and adds the rev-side conversions from the internal `ServiceInfo`
+//! runtime state and `RunRecord` history into the neutral wire `ServiceSnapshot`
+//! and `ServiceRun`.
 
 pub use wirebus_proto::*;
 
+use crate::init::services::RunRecord;
 use crate::parser::ServiceInfo;
+use crate::service::supervisor::Exit;
 
 impl From<&ServiceInfo> for ServiceSnapshot {
     fn from(info: &ServiceInfo) -> Self {
@@ -26,6 +29,31 @@ impl From<&ServiceInfo> for ServiceSnapshot {
             cpu_seconds: info.cpu_seconds,
             tasks: info.tasks,
             config_path: info.config_path.clone(),
+            health: info.health.map(|h| h.as_str().to_string()),
+        }
+    }
+}
+
+impl From<&RunRecord> for ServiceRun {
+    fn from(run: &RunRecord) -> Self {
+        let (exit_code, signal, core_dumped) = match run.exit {
+            Some(Exit::Code(code)) => (Some(code), None, false),
+            Some(Exit::Signal {
+                signal,
+                core_dumped,
+            }) => (None, Some(signal), core_dumped),
+            None => (None, None, false),
+        };
+        ServiceRun {
+            started: run.started.timestamp(),
+            ended: run.ended.map(|ts| ts.timestamp()),
+            pid: run.pid,
+            exit_code,
+            signal,
+            core_dumped,
+            restart_reason: run.restart_reason.clone(),
+            hook_failures: run.hook_failures.clone(),
+            oom_killed: run.oom_killed,
         }
     }
 }
@@ -15,6 +15,7 @@ use tokio::sync::Mutex;
 use super::policy::{self, Access, Operation, Principal, Scope, Tier};
 use super::protocol::{self, Message, MessageBody};
 use super::registry;
+use crate::service::jobs::JobKind;
 
 /// Make the System Highway socket reachable by any local process.
 ///
@@ -118,18 +119,11 @@ fn seat_session(principal: &Principal) -> Option<u64> {
 
 static NEXT_MSG_ID: AtomicU64 = AtomicU64::new(1);
 
-/// Maximum concurrent connections to a single bus. Each open connection holds
-/// one permit until it disconnects; long-lived peers (registered services,
-/// signal subscribers, a compositor's seat connection) each consume one, so the
-/// ceiling is set high enough not to starve a real system while still bounding a
-/// local connection flood on the world-connectable Highway.
-const MAX_BUS_CONNECTIONS: usize = 1024;
-
-fn next_id() -> u64 {
+pub(super) fn next_id() -> u64 {
     NEXT_MSG_ID.fetch_add(1, Ordering::Relaxed)
 }
 
-fn make_msg(request_id: u64, body: MessageBody) -> Message {
+pub(super) fn make_msg(request_id: u64, body: MessageBody) -> Message {
     Message {
         id: request_id,
         sender: "rev".to_string(),
@@ -192,7 +186,9 @@ fn launch_session(
                 .and_then(|u| u.get(username).ok())
                 .map(|a| a.uuid)
             {
-                crate::service::start_user_services(uid, gid, &session.lane_socket, &uuid);
+                crate::service::user_services::load(uid, gid, &session.lane_socket, &uuid);
+                // Pick up services the user installs while logged in.
+                crate::init::watch::watch_lane(uid, &crate::bus::lanes::user_service_dir(&uuid));
             }
             reply(
                 id,
@@ -211,8 +207,7 @@ fn launch_session(
 /// Connected clients keyed by their self-declared sender name.
 /// Used for signal delivery — when a signal fires, we look up subscribers
 /// and write to their writer halves.
-type ClientWriters =
-    Arc<Mutex<HashMap<String, tokio::sync::mpsc::Sender<Message>>>>;
+pub(super) type ClientWriters = Arc<Mutex<HashMap<String, tokio::sync::mpsc::Sender<Message>>>>;
 
 /// Run a WireBus server on `socket_path`. `tier` says whether this is the system
 /// Highway or a user Lane; it is threaded into every request so the policy can
@@ -242,14 +237,21 @@ pub async fn run(socket_path: &str, tier: Tier) -> std::io::Result<()> {
     // One registry per bus: the Highway's names and each Lane's names are
     // separate, so lanes cannot see one another's registrations.
     let registry = Arc::new(registry::Registry::new());
+    if matches!(tier, Tier::Highway) {
+        super::activation::set_highway(registry.clone());
+        super::signals::start(clients.clone(), registry.clone());
+        super::signals::reach_milestone("highway-ready");
+    }
 
     // Bound concurrent connections. The Highway is world-connectable, so without
     // a cap any local process could open connections without limit and exhaust
     // rev's memory and task scheduler. Each connection holds one permit for its
     // whole lifetime (a registered service or signal subscriber is long-lived),
     // so the limit is generous; excess connections are refused and the peer
-    // retries. Applies to Lanes too, where it only bounds a single user.
-    let conn_limit = Arc::new(tokio::sync::Semaphore::new(MAX_BUS_CONNECTIONS));
+    // retries. Applies to Lanes too, where it only bounds a single user. The
+    // limit is `max-bus-connections` in rev.toml, read when the bus starts.
+    let max_connections = crate::settings::get().max_bus_connections;
+    let conn_limit = Arc::new(tokio::sync::Semaphore::new(max_connections));
     let mut at_capacity = false;
 
     loop {
@@ -265,7 +267,7 @@ pub async fn run(socket_path: &str, tier: Tier) -> std::io::Result<()> {
                 if !at_capacity {
                     eprintln!(
                         "rev: wirebus at connection limit ({}); refusing new connections",
-                        MAX_BUS_CONNECTIONS
+                        max_connections
                     );
                     at_capacity = true;
                 }
@@ -475,6 +477,12 @@ fn classify(
         MessageBody::Lookup { .. }
         | MessageBody::ListBus
         | MessageBody::ListServices
+        | MessageBody::ListTimers
+        | MessageBody::TimerHistory { .. }
+        | MessageBody::ServiceHistory { .. }
+        | MessageBody::GetServiceStats { .. }
+        | MessageBody::CoredumpOwner { .. }
+        | MessageBody::ListLinger
         | MessageBody::ListSessions => Operation::Read,
 
         MessageBody::Subscribe { .. } | MessageBody::Unsubscribe { .. } => {
@@ -498,7 +506,18 @@ fn classify(
         MessageBody::StartService { .. }
         | MessageBody::StopService { .. }
         | MessageBody::ReloadService { .. }
+        | MessageBody::RestartService { .. }
+        | MessageBody::RunTransient { .. }
         | MessageBody::Rescan => Operation::ServiceControl { scope: service_scope },
+        // Lingering keeps a user's own services running: yours to set, another
+        // user's only with elevation.
+        MessageBody::SetLinger { uid, .. } => Operation::ServiceControl {
+            scope: if principal.uid() == Some(*uid) {
+                Scope::OwnUser(*uid)
+            } else {
+                Scope::SystemOrOtherUser
+            },
+        },
 
         MessageBody::OpenDevice { .. }
         | MessageBody::CloseDevice { .. }
@@ -556,19 +575,122 @@ async fn handle_message(
 
     let (response, pass_fd) = match &msg.body {
         // ----- Service management -----
+        // On a Lane these act on that user's own service table.
+        MessageBody::StartService { service }
+        | MessageBody::StopService { service }
+        | MessageBody::RestartService { service }
+        | MessageBody::ReloadService { service }
+            if let Tier::Lane { uid } = tier =>
+        {
+            handle_lane_service(id, uid, &msg.body, service)
+        }
+        MessageBody::Rescan if matches!(tier, Tier::Lane { .. }) => err_reply(
+            id,
+            "rescan reloads system services; user services are loaded at login",
+        ),
+        MessageBody::RunTransient { .. } if matches!(tier, Tier::Lane { .. }) => err_reply(
+            id,
+            "transient services run on the Highway, as system services",
+        ),
+        MessageBody::ListServices if let Tier::Lane { uid } = tier => {
+            let services = crate::service::user_services::list(uid)
+                .into_iter()
+                .map(|(name, mut info)| {
+                    crate::service::accounting::refresh(&mut info);
+                    (name, protocol::ServiceSnapshot::from(&info))
+                })
+                .collect();
+            reply(id, MessageBody::ServiceList { services })
+        }
         MessageBody::StartService { service } => handle_start_service(id, service),
-        MessageBody::StopService { service } => handle_stop_service(id, service),
-        MessageBody::ReloadService { service } => handle_reload_service(id, service),
+        MessageBody::StopService { service } => queue_job(id, service, JobKind::Stop),
+        MessageBody::RestartService { service } => queue_job(id, service, JobKind::Restart),
+        MessageBody::ReloadService { service } => queue_job(id, service, JobKind::Reload),
+        MessageBody::RunTransient {
+            name,
+            command,
+            env,
+            user,
+            limits,
+            restart_policy,
+        } => {
+            use crate::service::transient;
+            let config = transient::config(
+                name,
+                command,
+                env,
+                user.as_deref(),
+                limits,
+                restart_policy.as_deref(),
+            );
+            // Starting resolves the user, runs hooks and forks; keep it off
+            // the reactor like every other start.
+            let started = match config {
+                Ok(config) => tokio::task::spawn_blocking(move || transient::run(config))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("start task failed: {e}"))),
+                Err(e) => Err(e),
+            };
+            match started {
+                Ok(Some(pid)) => ok_reply(id, format!("Started {} (PID {})", name, pid)),
+                Ok(None) => ok_reply(id, format!("Started {}; it has already exited", name)),
+                Err(e) => err_reply(id, e),
+            }
+        }
         MessageBody::ListServices => {
             let services = crate::init::services::list_services()
-                .iter()
-                .map(|(name, info)| (name.clone(), protocol::ServiceSnapshot::from(info)))
+                .into_iter()
+                .map(|(name, mut info)| {
+                    crate::service::accounting::refresh(&mut info);
+                    (name, protocol::ServiceSnapshot::from(&info))
+                })
                 .collect();
             reply(id, MessageBody::ServiceList { services })
         }
+        MessageBody::ListTimers => {
+            let timers = crate::service::scheduler::list_timers();
+            reply(id, MessageBody::TimerList { timers })
+        }
+        MessageBody::TimerHistory { service } => {
+            let runs = crate::service::scheduler::history(service);
+            reply(id, MessageBody::TimerRuns { runs })
+        }
+        MessageBody::ServiceHistory { service } => {
+            let runs: Vec<_> = crate::init::services::run_history(service)
+                .iter()
+                .map(protocol::ServiceRun::from)
+                .collect();
+            if runs.is_empty() && crate::init::services::get_service(service).is_none() {
+                err_reply(id, format!("service '{}' not found", service))
+            } else {
+                reply(id, MessageBody::ServiceRuns { runs })
+            }
+        }
+        MessageBody::GetServiceStats { service } => {
+            match crate::init::services::get_service(service) {
+                Some(info) => {
+                    let stats = crate::service::accounting::collect(&info);
+                    reply(id, MessageBody::ServiceStatsResult { stats })
+                }
+                None => err_reply(id, format!("service '{}' not found", service)),
+            }
+        }
+        MessageBody::CoredumpOwner { pid } => {
+            use crate::service::coredump::Policy;
+            let policy = crate::init::services::get_service_by_pid(*pid)
+                .map_or_else(Policy::unowned, |info| Policy::for_service(&info.config));
+            reply(
+                id,
+                MessageBody::CoredumpPolicy {
+                    service: policy.service,
+                    size_max: policy.size_max,
+                    keep: policy.keep,
+                },
+            )
+        }
         MessageBody::Rescan => {
-            let found = rescan_services();
-            ok_reply(id, format!("Rescanned: found {} new services", found))
+            let report = crate::init::reload::reload();
+            ok_reply(id, report.summary())
         }
 
         // ----- Bus registry -----
@@ -598,14 +720,20 @@ async fn handle_message(
                 let _ = crate::bus::activation::activate(name, registry).await;
             }
             match registry.lookup(name) {
-                Some(reg) => reply(
-                    id,
-                    MessageBody::LookupResult {
-                        name: reg.name,
-                        socket_path: reg.socket_path,
-                        methods: reg.methods,
-                    },
-                ),
+                Some(reg) => {
+                    // The only sign of use rev gets for an idle-exit service.
+                    if matches!(tier, Tier::Highway) {
+                        crate::service::idle::looked_up(name);
+                    }
+                    reply(
+                        id,
+                        MessageBody::LookupResult {
+                            name: reg.name,
+                            socket_path: reg.socket_path,
+                            methods: reg.methods,
+                        },
+                    )
+                }
                 None => err_reply(id, format!("service '{}' not found on bus", name)),
             }
         }
@@ -617,7 +745,12 @@ async fn handle_message(
         // ----- Signals -----
         MessageBody::Subscribe { service, signal } => {
             match registry.subscribe(&msg.sender, service, signal) {
-                Ok(()) => ok_reply(id, format!("Subscribed to {}:{}", service, signal)),
+                Ok(()) => {
+                    if service == "rev" && matches!(tier, Tier::Highway) {
+                        super::signals::replay_milestones(clients, &msg.sender, signal).await;
+                    }
+                    ok_reply(id, format!("Subscribed to {}:{}", service, signal))
+                }
                 Err(e) => err_reply(id, e),
             }
         }
@@ -766,7 +899,9 @@ async fn handle_message(
             let owner = crate::session::owner_uid(*session_id);
             match crate::session::end_session(*session_id) {
                 Ok(()) => {
-                    if let Some(uid) = owner {
+                    if let Some(uid) = owner
+                        && !crate::session::linger::is_lingering(uid)
+                    {
                         let _ = crate::bus::lanes::LANES.stop_lane(uid);
                     }
                     ok_reply(id, format!("Session {} ended", session_id))
@@ -774,6 +909,30 @@ async fn handle_message(
                 Err(e) => err_reply(id, e),
             }
         }
+        MessageBody::SetLinger { uid, enable } => {
+            match crate::session::linger::set(*uid, *enable) {
+                Ok(()) => ok_reply(
+                    id,
+                    format!(
+                        "Linger {} for uid {}",
+                        if *enable { "enabled" } else { "disabled" },
+                        uid
+                    ),
+                ),
+                Err(e) => err_reply(id, e),
+            }
+        }
+        MessageBody::ListLinger => {
+            // As for ListSessions: the system sees every lingering uid, a user
+            // only their own.
+            let all = matches!(principal, Principal::System);
+            let viewer = principal.uid();
+            let uids = crate::session::linger::list()
+                .into_iter()
+                .filter(|uid| all || Some(*uid) == viewer)
+                .collect();
+            reply(id, MessageBody::LingerList { uids })
+        }
         MessageBody::ListSessions => {
             // Enumerating other users' sessions is cross-scope: the system sees
             // all, a user sees only their own. This keeps the Highway being
@@ -803,52 +962,58 @@ async fn handle_message(
 }
 
 fn handle_start_service(id: u64, name: &str) -> (Message, Option<RawFd>) {
-    let (app_id, _service, file) = match crate::cli::parse_service::parse_service(name) {
-        Ok(v) => v,
-        Err(e) => return err_reply(id, format!("invalid service name: {}", e)),
-    };
-    let service_dir = std::path::PathBuf::from(format!("./Services/{}", app_id));
-    match file.file_name() {
-        Some(filename) => {
-            crate::service::start_service_from_path(&service_dir.join(filename));
-            ok_reply(id, format!("Started service: {}", name))
-        }
-        None => err_reply(id, "invalid service file path"),
+    // A service rev has not loaded is started from its app's directory, so the
+    // name must be a valid `app.id/service`; check it now rather than fail the
+    // job later.
+    if crate::init::services::get_service(name).is_none()
+        && let Err(e) = crate::cli::parse_service::parse_service(name)
+    {
+        return err_reply(id, format!("invalid service name: {}", e));
     }
+    queue_job(id, name, JobKind::Start)
 }
 
-fn handle_stop_service(id: u64, name: &str) -> (Message, Option<RawFd>) {
-    match crate::init::services::get_service(name) {
-        Some(info) => {
-            crate::service::stop_service(&info);
-            ok_reply(id, format!("Stopped service: {}", name))
+/// Start, stop or restart a service in the user service table of `uid`'s lane.
+/// Unlike system services these act at once rather than through the job queue.
+fn handle_lane_service(
+    id: u64,
+    uid: u32,
+    body: &MessageBody,
+    service: &str,
+) -> (Message, Option<RawFd>) {
+    use crate::service::user_services;
+    let result = match body {
+        MessageBody::StartService { .. } => user_services::start(uid, service)
+            .map(|pid| format!("Started {} (PID {})", service, pid)),
+        MessageBody::StopService { .. } => {
+            user_services::stop(uid, service).map(|()| format!("Stopping {}", service))
         }
-        None => err_reply(id, format!("service '{}' not found", name)),
+        MessageBody::RestartService { .. } => {
+            user_services::restart(uid, service).map(|()| format!("Restarting {}", service))
+        }
+        _ => Err("reload is not supported for user services; restart instead".to_string()),
+    };
+    match result {
+        Ok(message) => ok_reply(id, message),
+        Err(e) => err_reply(id, e),
     }
 }
 
-fn handle_reload_service(id: u64, name: &str) -> (Message, Option<RawFd>) {
-    match crate::init::services::get_service(name) {
-        Some(info) => {
-            if let Some(ref reload_cmd) = info.config.exec_reload {
-                if info.pid.is_some() {
-                    crate::service::run_hook(reload_cmd, &info.config);
-                    ok_reply(id, format!("Reloaded service: {}", name))
-                } else {
-                    err_reply(id, format!("service '{}' is not running", name))
-                }
-            } else if let Some(pid) = info.pid {
-                // Fallback: send SIGHUP
-                unsafe {
-                    libc::kill(pid as i32, libc::SIGHUP);
-                }
-                ok_reply(id, format!("Sent SIGHUP to service: {}", name))
-            } else {
-                err_reply(id, format!("service '{}' is not running", name))
-            }
-        }
-        None => err_reply(id, format!("service '{}' not found", name)),
+/// Queue a control job and reply with its id at once. The outcome arrives later
+/// as a `JobFinished` signal from `rev`.
+fn queue_job(id: u64, name: &str, kind: JobKind) -> (Message, Option<RawFd>) {
+    if kind != JobKind::Start && crate::init::services::get_service(name).is_none() {
+        return err_reply(id, format!("service '{}' not found", name));
     }
+    let job_id = crate::service::jobs::enqueue(name, kind);
+    reply(
+        id,
+        MessageBody::JobQueued {
+            job_id,
+            service: name.to_string(),
+            kind: kind.as_str().to_string(),
+        },
+    )
 }
 
 /// Restore the VT to text mode. Called on behalf of unprivileged compositors.
@@ -899,49 +1064,6 @@ fn restore_vt() {
     println!("rev: VT restored to text mode");
 }
 
-fn rescan_services() -> u32 {
-    let dirs = crate::parser::service_dirs();
-    let mut found = 0u32;
-    for dir in dirs {
-        if !dir.exists() {
-            continue;
-        }
-        for entry in walkdir::WalkDir::new(&dir) {
-            let entry = match entry {
-                Ok(e) => e,
-                Err(_) => continue,
-            };
-            let path = entry.path();
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("rsc") {
-                let name = path
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("unknown");
-                if crate::init::services::get_service(name).is_none() {
-                    if let Ok(text) = std::fs::read_to_string(path) {
-                        if let Ok(config) =
-                            toml::from_str::<crate::parser::ServiceConfig>(&text)
-                        {
-                            let svc_name = config.name.clone();
-                            crate::init::services::register_service(
-                                svc_name,
-                                crate::parser::ServiceInfo {
-                                    name: config.name.clone(),
-                                    config_path: Some(path.display().to_string()),
-                                    config,
-                                    ..Default::default()
-                                },
-                            );
-                            found += 1;
-                        }
-                    }
-                }
-            }
-        }
-    }
-    found
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
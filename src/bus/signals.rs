@@ -0,0 +1,193 @@
+//! Signals rev itself emits on the Highway, as source `rev`.
+//!
+//! They go through the same subscriber machinery as a client's `EmitSignal`:
+//! subscribe with `Subscribe { service: "rev", signal }` (or `"*"`). Payloads
+//! are MessagePack-encoded structs from `wirebus-proto`:
+//!
+//! | Signal | Payload |
+//! |--------|---------|
+//! | `ServiceStateChanged` | `ServiceStateChange` |
+//! | `ServiceAdded`, `ServiceRemoved` | `ServiceRef` |
+//! | `BootMilestoneReached` | `BootMilestone` |
+//! | `JobFinished` | `JobResult` |
+//!
+//! Boot milestones are latched: a client that subscribes after a milestone was
+//! reached gets it replayed at once, since most are reached before anyone could
+//! have connected.
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::Serialize;
+
+use super::protocol::{BootMilestone, MessageBody, ServiceRef, ServiceStateChange};
+use super::registry::Registry;
+use super::server::{ClientWriters, make_msg, next_id};
+use crate::init::services::ServiceEvent;
+
+/// Signals waiting for the Highway's fan-out task. Set when the Highway starts.
+static OUTBOX: OnceLock<tokio::sync::mpsc::UnboundedSender<(String, Vec<u8>)>> = OnceLock::new();
+
+/// Milestones reached so far, in order.
+static MILESTONES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn encode(payload: &impl Serialize) -> Option<Vec<u8>> {
+    rmp_serde::to_vec_named(payload)
+        .map_err(|e| eprintln!("rev: cannot encode signal payload: {}", e))
+        .ok()
+}
+
+fn delivery(signal: &str, payload: Vec<u8>) -> super::protocol::Message {
+    make_msg(
+        next_id(),
+        MessageBody::SignalDelivery {
+            source: "rev".to_string(),
+            signal: signal.to_string(),
+            payload,
+        },
+    )
+}
+
+/// Emit `signal` from rev to the Highway's subscribers of `rev`. Callable from
+/// any thread; dropped silently before the Highway is up.
+pub fn emit(signal: &str, payload: &impl Serialize) {
+    if let (Some(tx), Some(bytes)) = (OUTBOX.get(), encode(payload)) {
+        let _ = tx.send((signal.to_string(), bytes));
+    }
+}
+
+/// Record that boot reached `milestone` and announce it.
+pub fn reach_milestone(milestone: &str) {
+    MILESTONES
+        .lock()
+        .expect("milestones lock poisoned")
+        .push(milestone.to_string());
+    println!("rev: boot milestone {} reached", milestone);
+    emit(
+        "BootMilestoneReached",
+        &BootMilestone {
+            milestone: milestone.to_string(),
+        },
+    );
+}
+
+/// Send the milestones reached so far to a client that just subscribed to
+/// `signal` from `rev`.
+pub async fn replay_milestones(clients: &ClientWriters, subscriber: &str, signal: &str) {
+    if signal != "BootMilestoneReached" && signal != "*" {
+        return;
+    }
+    let reached = MILESTONES.lock().expect("milestones lock poisoned").clone();
+    let clients = clients.lock().await;
+    let Some(tx) = clients.get(subscriber) else {
+        return;
+    };
+    for milestone in reached {
+        if let Some(bytes) = encode(&BootMilestone { milestone }) {
+            let _ = tx.try_send(delivery("BootMilestoneReached", bytes));
+        }
+    }
+}
+
+/// The signal a service table event is published as.
+fn service_signal(event: &ServiceEvent) -> (&'static str, Option<Vec<u8>>) {
+    match event {
+        ServiceEvent::Added { service } => (
+            "ServiceAdded",
+            encode(&ServiceRef {
+                service: service.clone(),
+            }),
+        ),
+        ServiceEvent::Removed { service } => (
+            "ServiceRemoved",
+            encode(&ServiceRef {
+                service: service.clone(),
+            }),
+        ),
+        ServiceEvent::StateChanged {
+            service,
+            old,
+            new,
+            pid,
+            exit_code,
+            reason,
+        } => (
+            "ServiceStateChanged",
+            encode(&ServiceStateChange {
+                service: service.clone(),
+                old: old.as_str().to_string(),
+                new: new.as_str().to_string(),
+                pid: *pid,
+                exit_code: *exit_code,
+                reason: reason.clone(),
+            }),
+        ),
+    }
+}
+
+/// Start delivering rev's signals on the Highway, and publish service table
+/// events as signals.
+pub fn start(clients: ClientWriters, registry: Arc<Registry>) {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<(String, Vec<u8>)>();
+    if OUTBOX.set(tx.clone()).is_err() {
+        return;
+    }
+    tokio::spawn(async move {
+        while let Some((signal, payload)) = rx.recv().await {
+            let message = delivery(&signal, payload);
+            let clients_map = clients.lock().await;
+            for sub_name in registry.get_signal_subscribers("rev", &signal) {
+                if let Some(tx) = clients_map.get(&sub_name) {
+                    let _ = tx.try_send(message.clone());
+                }
+            }
+        }
+    });
+
+    let mut events = crate::init::services::subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let (signal, Some(bytes)) = service_signal(&event) {
+                        let _ = tx.send((signal.to_string(), bytes));
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    eprintln!("rev: dropped {} service events for bus subscribers", n);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ServiceState;
+
+    #[test]
+    fn state_changes_are_published_with_their_details() {
+        let event = ServiceEvent::StateChanged {
+            service: "sshd".to_string(),
+            old: ServiceState::Running,
+            new: ServiceState::Failed,
+            pid: Some(812),
+            exit_code: Some(255),
+            reason: "exited with code 255".to_string(),
+        };
+        let (signal, bytes) = service_signal(&event);
+        assert_eq!(signal, "ServiceStateChanged");
+        let change: ServiceStateChange = rmp_serde::from_slice(&bytes.unwrap()).unwrap();
+        assert_eq!(
+            (change.old.as_str(), change.new.as_str()),
+            ("running", "failed")
+        );
+        assert_eq!((change.pid, change.exit_code), (Some(812), Some(255)));
+
+        let (signal, _) = service_signal(&ServiceEvent::Removed {
+            service: "sshd".to_string(),
+        });
+        assert_eq!(signal, "ServiceRemoved");
+    }
+}
@@ -0,0 +1,106 @@
+//! `rev coredump`: inspect the cores rev captured, and the hidden
+//! `coredump-handler` the kernel runs for each crash.
+
+use super::timers::format_time;
+use crate::service::coredump::{self, CoreRecord};
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Action {
+    /// List stored cores, oldest first.
+    List {
+        /// Only this service's cores.
+        service_name: Option<String>,
+    },
+    /// Show everything recorded about one core.
+    Info { id: String },
+    /// Copy a stored core out, e.g. to load it into a debugger.
+    Extract {
+        id: String,
+        /// Where to write the core.
+        output: String,
+    },
+}
+
+fn signal_name(signal: i32) -> String {
+    nix::sys::signal::Signal::try_from(signal)
+        .map(|s| s.as_str().to_string())
+        .unwrap_or_else(|_| signal.to_string())
+}
+
+fn find(id: &str) -> CoreRecord {
+    match coredump::find(id) {
+        Some(record) => record,
+        None => {
+            eprintln!("rev: no core with id {}", id);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn run(action: Action) {
+    match action {
+        Action::List { service_name } => list(service_name.as_deref()),
+        Action::Info { id } => info(&id),
+        Action::Extract { id, output } => extract(&id, &output),
+    }
+}
+
+fn list(service: Option<&str>) {
+    let records = coredump::list(service);
+    if records.is_empty() {
+        println!("No stored cores.");
+        return;
+    }
+    println!(
+        "{:<20}  {:<24}  {:<8}  {:<10}  {:<12}  SERVICE",
+        "TIME", "ID", "PID", "SIGNAL", "SIZE"
+    );
+    for r in records {
+        println!(
+            "{:<20}  {:<24}  {:<8}  {:<10}  {:<12}  {}",
+            format_time(Some(r.timestamp)),
+            r.id,
+            r.pid,
+            signal_name(r.signal),
+            if r.truncated {
+                format!("{}+", r.size)
+            } else {
+                r.size.to_string()
+            },
+            r.service.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+fn info(id: &str) {
+    let r = find(id);
+    println!("Id:         {}", r.id);
+    println!("Service:    {}", r.service.as_deref().unwrap_or("-"));
+    println!("Executable: {}", r.comm);
+    println!("PID:        {}", r.pid);
+    println!("Signal:     {} ({})", signal_name(r.signal), r.signal);
+    println!("Time:       {}", format_time(Some(r.timestamp)));
+    match r.core_path() {
+        Some(path) => println!(
+            "Core:       {} ({} bytes{})",
+            path.display(),
+            r.size,
+            if r.truncated { ", truncated" } else { "" }
+        ),
+        None => println!("Core:       not stored"),
+    }
+}
+
+fn extract(id: &str, output: &str) {
+    let Some(path) = find(id).core_path() else {
+        eprintln!("rev: core {} was not stored", id);
+        std::process::exit(1);
+    };
+    match std::fs::copy(&path, output) {
+        Ok(bytes) => println!("Wrote {} bytes to {}", bytes, output),
+        Err(e) => {
+            eprintln!("rev: cannot extract core {} to {}: {}", id, output, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! `rev history <service>`: the service's recent runs, newest first, with how
+//! each one ended and why it was started.
+
+use super::timers::format_time;
+use crate::bus::protocol::{MessageBody, ServiceRun};
+use crate::service::supervisor::Exit;
+
+fn result(run: &ServiceRun) -> String {
+    let exit = match (run.exit_code, run.signal) {
+        (Some(code), _) => Some(Exit::Code(code)),
+        (None, Some(signal)) => Some(Exit::Signal {
+            signal,
+            core_dumped: run.core_dumped,
+        }),
+        (None, None) => None,
+    };
+    if run.oom_killed {
+        return "oom-kill".to_string();
+    }
+    match (exit, run.pid, run.ended) {
+        (Some(exit), _, _) => exit.describe(),
+        (None, None, Some(_)) => "did not start".to_string(),
+        (None, _, None) => "running".to_string(),
+        (None, Some(_), Some(_)) => "unknown".to_string(),
+    }
+}
+
+pub async fn run(service: &str) {
+    let request = MessageBody::ServiceHistory {
+        service: service.to_string(),
+    };
+    let runs = match super::service_client::query(request).await {
+        MessageBody::ServiceRuns { runs } => runs,
+        _ => {
+            eprintln!("rev: unexpected response");
+            std::process::exit(1);
+        }
+    };
+    if runs.is_empty() {
+        println!("No recorded runs for {}.", service);
+        return;
+    }
+    println!(
+        "{:<20}  {:<20}  {:<8}  {:<32}  NOTES",
+        "STARTED", "ENDED", "PID", "RESULT"
+    );
+    for run in runs.iter().rev() {
+        let mut notes: Vec<String> = run.hook_failures.clone();
+        if let Some(ref reason) = run.restart_reason {
+            notes.insert(0, format!("restarted: {}", reason));
+        }
+        println!(
+            "{:<20}  {:<20}  {:<8}  {:<32}  {}",
+            format_time(Some(run.started)),
+            format_time(run.ended),
+            run.pid.map_or("-".to_string(), |p| p.to_string()),
+            result(run),
+            if notes.is_empty() {
+                "-".to_string()
+            } else {
+                notes.join("; ")
+            }
+        );
+    }
+}
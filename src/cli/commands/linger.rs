@@ -0,0 +1,73 @@
+//! `rev linger`: keep a user's Lane and user services running without a login,
+//! and start them at boot.
+
+use crate::bus::protocol::MessageBody;
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Action {
+    /// Let a user's services run without a session. Defaults to yourself.
+    Enable { user: Option<String> },
+    /// Stop lingering; the user's services then run only while logged in.
+    Disable { user: Option<String> },
+    /// List the users who linger (only yourself, unless run as root).
+    List,
+}
+
+/// The uid `user` names (a uid or an account name), or the caller's own.
+fn resolve(user: Option<&str>) -> u32 {
+    let Some(user) = user else {
+        return nix::unistd::getuid().as_raw();
+    };
+    if let Ok(uid) = user.parse() {
+        return uid;
+    }
+    match uac_core::Uac::open().and_then(|uac| uac.get(user)) {
+        Ok(account) => account.uid,
+        Err(e) => {
+            eprintln!("rev: unknown user '{}': {}", user, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The account name of `uid`, for display; the uid itself if UAC has none.
+fn name_of(uid: u32) -> String {
+    uac_core::Uac::open()
+        .and_then(|uac| uac.name_by_uid(uid))
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| uid.to_string())
+}
+
+pub async fn run(action: Action) {
+    let (user, enable) = match action {
+        Action::Enable { user } => (user, true),
+        Action::Disable { user } => (user, false),
+        Action::List => {
+            let uids = match super::service_client::query(MessageBody::ListLinger).await {
+                MessageBody::LingerList { uids } => uids,
+                _ => {
+                    eprintln!("rev: unexpected response");
+                    std::process::exit(1);
+                }
+            };
+            if uids.is_empty() {
+                println!("No lingering users.");
+            }
+            for uid in uids {
+                println!("{:<8}  {}", uid, name_of(uid));
+            }
+            return;
+        }
+    };
+    let uid = resolve(user.as_deref());
+    let body = MessageBody::SetLinger { uid, enable };
+    // Your own flag needs no elevation; another user's does.
+    if uid == nix::unistd::getuid().as_raw() {
+        if let MessageBody::Ok { message } = super::service_client::query(body).await {
+            println!("{}", message);
+        }
+    } else {
+        super::service_client::send_elevated(body, false).await;
+    }
+}
@@ -1,15 +1,25 @@
+mod coredump;
 mod create;
+mod history;
 mod install;
+mod linger;
 mod read;
+mod reload;
+mod restart;
+mod run;
 mod service_client;
 mod start;
 mod stop;
+mod timers;
 
 //create Commands enum
 #[derive(Debug, clap::Subcommand)]
 pub enum Commands {
     Start {
         service_name: String,
+        /// Wait until the start job finishes and report its result.
+        #[arg(long)]
+        wait: bool,
     },
     RunAsInit {
       // auto_start cant be bool because clap doesnt support bool args, and makes them required flags.
@@ -24,6 +34,54 @@ pub enum Commands {
     },
     Stop {
         service_name: String,
+        /// Wait until the stop job finishes and report its result.
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Stop a service and start it again.
+    Restart {
+        service_name: String,
+        /// Wait until the restart job finishes and report its result.
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Run a command as a transient service: supervised, logged, listed and
+    /// stoppable like any other, and gone from the table once it exits.
+    Run(run::Args),
+    /// Re-read every .rsc file and apply changed, added and removed service
+    /// definitions. Lists running services that need a restart to pick up
+    /// their new definition.
+    Reload,
+    /// List scheduled and timer-started services with their next and last run,
+    /// or the recorded runs of one service.
+    Timers {
+        /// Show this service's run history instead.
+        service_name: Option<String>,
+    },
+    /// Show a service's recent runs: when each started and ended, how it
+    /// exited, and any restart reason or failed hook.
+    History {
+        service_name: String,
+    },
+    /// Inspect the core dumps rev captured from crashed processes.
+    Coredump {
+        #[command(subcommand)]
+        action: coredump::Action,
+    },
+    /// The kernel's core dump handler (`core_pattern`): stores the core read
+    /// from stdin. Not meant to be run by hand.
+    #[command(hide = true)]
+    CoredumpHandler {
+        pid: u32,
+        signal: i32,
+        timestamp: i64,
+        comm: String,
+    },
+    /// Keep a user's Lane and user services running without a login, and
+    /// start them at boot.
+    Linger {
+        #[command(subcommand)]
+        action: linger::Action,
     },
     /// Validate a .rsc service file and install it: system-wide into
     /// /Construct/Services, or per-user (--user) into the account vault.
@@ -52,8 +110,8 @@ pub async fn execute_command(command: Commands) {
         Commands::Create { service_name } => {
             create::run(&service_name);
         }
-        Commands::Start { service_name } => {
-            start::run(&service_name).await;
+        Commands::Start { service_name, wait } => {
+            start::run(&service_name, wait).await;
         }
         Commands::RunAsInit { auto_start } => {
             crate::init::run(auto_start == 1).await;
@@ -61,8 +119,37 @@ pub async fn execute_command(command: Commands) {
         Commands::Read { service_name } => {
             read::run(&service_name);
         }
-        Commands::Stop { service_name } => {
-            stop::run(&service_name).await;
+        Commands::Stop { service_name, wait } => {
+            stop::run(&service_name, wait).await;
+        }
+        Commands::Restart { service_name, wait } => {
+            restart::run(&service_name, wait).await;
+        }
+        Commands::Run(args) => {
+            run::run(args).await;
+        }
+        Commands::Reload => {
+            reload::run().await;
+        }
+        Commands::Timers { service_name } => {
+            timers::run(service_name.as_deref()).await;
+        }
+        Commands::History { service_name } => {
+            history::run(&service_name).await;
+        }
+        Commands::Coredump { action } => {
+            coredump::run(action);
+        }
+        Commands::CoredumpHandler {
+            pid,
+            signal,
+            timestamp,
+            comm,
+        } => {
+            crate::service::coredump::handle(pid, signal, timestamp, &comm);
+        }
+        Commands::Linger { action } => {
+            linger::run(action).await;
         }
         Commands::Install { file, user } => {
             install::run(&file, user);
@@ -0,0 +1,5 @@
+use crate::bus::protocol::MessageBody;
+
+pub async fn run() {
+    super::service_client::send_elevated(MessageBody::Rescan, false).await;
+}
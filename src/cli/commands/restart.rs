@@ -0,0 +1,11 @@
+use crate::bus::protocol::MessageBody;
+
+pub async fn run(service_name: &str, wait: bool) {
+    super::service_client::send_elevated(
+        MessageBody::RestartService {
+            service: service_name.to_string(),
+        },
+        wait,
+    )
+    .await;
+}
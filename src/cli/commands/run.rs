@@ -0,0 +1,60 @@
+//! `rev run`: run a command as a transient system service, supervised, logged
+//! and stoppable like one loaded from a .rsc file.
+
+use std::collections::HashMap;
+
+use crate::bus::protocol::{MessageBody, ResourceLimits};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Name of the service; `run-<pid>` by default.
+    #[arg(long)]
+    name: Option<String>,
+    /// Run the command as this user (account name or uid).
+    #[arg(long)]
+    user: Option<String>,
+    /// Set an environment variable, as KEY=VALUE. May be repeated.
+    #[arg(long = "env", short = 'e', value_name = "KEY=VALUE")]
+    env: Vec<String>,
+    /// Cap its memory at this many MiB.
+    #[arg(long)]
+    memory_max_mb: Option<u64>,
+    /// Cap its CPU time at this percentage of one CPU.
+    #[arg(long)]
+    cpu_quota: Option<u32>,
+    /// Cap its number of processes and threads.
+    #[arg(long)]
+    tasks_max: Option<u32>,
+    /// Restart policy: always, on-failure or never (the default).
+    #[arg(long)]
+    restart: Option<String>,
+    /// The command and its arguments.
+    #[arg(required = true, last = true)]
+    command: Vec<String>,
+}
+
+pub async fn run(args: Args) {
+    let mut env = HashMap::new();
+    for pair in &args.env {
+        let Some((key, value)) = pair.split_once('=') else {
+            eprintln!("rev: --env expects KEY=VALUE, got '{}'", pair);
+            std::process::exit(1);
+        };
+        env.insert(key.to_string(), value.to_string());
+    }
+    let body = MessageBody::RunTransient {
+        name: args
+            .name
+            .unwrap_or_else(|| format!("run-{}", std::process::id())),
+        command: args.command,
+        env,
+        user: args.user,
+        limits: ResourceLimits {
+            memory_max_mb: args.memory_max_mb,
+            cpu_quota: args.cpu_quota,
+            tasks_max: args.tasks_max,
+        },
+        restart_policy: args.restart,
+    };
+    super::service_client::send_elevated(body, false).await;
+}
@@ -1,4 +1,4 @@
-//! Shared client path for system-service control (start/stop).
+//! Shared client path for system-service control (start/stop/restart).
 //!
 //! `rev start`/`rev stop` act on the system Highway, where controlling a system
 //! service is a cross-scope action. A root caller is authorized by its
@@ -6,11 +6,14 @@
 //! SystemServiceControl token from RookGuard (the same handshake `sudo` uses)
 //! and attach it to the request. The rev daemon verifies it at the choke point.
 
-use crate::bus::protocol::{self, Message, MessageBody};
+use crate::bus::protocol::{self, JobResult, Message, MessageBody};
 use tokio::net::UnixStream;
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
 
 /// Send a service-control request, elevating first when the caller is not root.
-pub async fn send_elevated(body: MessageBody) {
+/// Control requests are queued as jobs; with `wait`, block until the job
+/// finishes and exit non-zero if it failed or was cancelled.
+pub async fn send_elevated(body: MessageBody, wait: bool) {
     let auth_token = match obtain_token().await {
         Ok(t) => t,
         Err(e) => {
@@ -19,19 +22,38 @@ pub async fn send_elevated(body: MessageBody) {
         }
     };
 
-    let socket_path = crate::bus::socket_path();
-    let stream = match UnixStream::connect(&socket_path).await {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("rev: cannot connect to wirebus ({}): {}", socket_path.display(), e);
+    let (mut reader, mut writer) = connect().await;
+    // A waiting client needs a bus name of its own: signals are delivered by
+    // sender label, and two `rev start --wait` may run at once.
+    let sender = if wait {
+        format!("rev-cli-{}", std::process::id())
+    } else {
+        "rev-cli".to_string()
+    };
+    // Subscribe before queueing so a job that finishes at once is not missed.
+    if wait {
+        let msg = Message {
+            id: 1,
+            sender: sender.clone(),
+            auth_token: None,
+            body: MessageBody::Subscribe {
+                service: "rev".to_string(),
+                signal: "JobFinished".to_string(),
+            },
+        };
+        if let Err(e) = protocol::send_message(&mut writer, &msg).await {
+            eprintln!("rev: failed to subscribe to job results: {}", e);
             std::process::exit(1);
         }
-    };
-    let (mut reader, mut writer) = stream.into_split();
+        if let Err(e) = protocol::recv_message(&mut reader).await {
+            eprintln!("rev: failed to read response: {}", e);
+            std::process::exit(1);
+        }
+    }
 
     let msg = Message {
-        id: 1,
-        sender: "rev-cli".to_string(),
+        id: 2,
+        sender: sender.clone(),
         auth_token,
         body,
     };
@@ -40,17 +62,93 @@ pub async fn send_elevated(body: MessageBody) {
         std::process::exit(1);
     }
 
-    match protocol::recv_message(&mut reader).await {
-        Ok(response) => match response.body {
-            MessageBody::Ok { message } => println!("{}", message),
+    // Read up to the reply; a JobFinished delivered first is kept for the wait.
+    let mut finished: Vec<JobResult> = Vec::new();
+    let job_id = loop {
+        let response = match protocol::recv_message(&mut reader).await {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("rev: failed to read response: {}", e);
+                std::process::exit(1);
+            }
+        };
+        match response.body {
+            MessageBody::JobQueued {
+                job_id,
+                service,
+                kind,
+            } => {
+                println!("Queued job {} ({} {})", job_id, kind, service);
+                break job_id;
+            }
+            MessageBody::Ok { message } => {
+                println!("{}", message);
+                return;
+            }
             MessageBody::Error { message } => {
                 eprintln!("rev: {}", message);
                 std::process::exit(1);
             }
-            _ => eprintln!("rev: unexpected response"),
+            MessageBody::SignalDelivery { payload, .. } => {
+                finished.extend(rmp_serde::from_slice::<JobResult>(&payload).ok());
+            }
+            _ => {
+                eprintln!("rev: unexpected response");
+                return;
+            }
+        }
+    };
+    if !wait {
+        return;
+    }
+
+    let result = loop {
+        if let Some(pos) = finished.iter().position(|r| r.job_id == job_id) {
+            break finished.swap_remove(pos);
+        }
+        match protocol::recv_message(&mut reader).await {
+            Ok(Message {
+                body: MessageBody::SignalDelivery { payload, .. },
+                ..
+            }) => finished.extend(rmp_serde::from_slice::<JobResult>(&payload).ok()),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!(
+                    "rev: lost connection while waiting for job {}: {}",
+                    job_id, e
+                );
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // Best effort: the subscription would otherwise outlive this process.
+    let unsubscribe = Message {
+        id: 3,
+        sender,
+        auth_token: None,
+        body: MessageBody::Unsubscribe {
+            service: "rev".to_string(),
+            signal: "JobFinished".to_string(),
         },
+    };
+    let _ = protocol::send_message(&mut writer, &unsubscribe).await;
+
+    if result.result == "done" {
+        println!("{}", result.message);
+    } else {
+        eprintln!("rev: job {} {}: {}", job_id, result.result, result.message);
+        std::process::exit(1);
+    }
+}
+
+/// Connect to the system Highway. Exits on failure.
+async fn connect() -> (OwnedReadHalf, OwnedWriteHalf) {
+    let socket_path = crate::bus::socket_path();
+    match UnixStream::connect(&socket_path).await {
+        Ok(s) => s.into_split(),
         Err(e) => {
-            eprintln!("rev: failed to read response: {}", e);
+            eprintln!("rev: cannot connect to wirebus ({}): {}", socket_path.display(), e);
             std::process::exit(1);
         }
     }
@@ -83,3 +181,34 @@ async fn obtain_token() -> Result<Option<String>, String> {
     .await
     .map_err(|e| format!("elevation task failed: {e}"))?
 }
+
+/// Send a read-only query and return the reply. Reads need no elevation, so no
+/// token is obtained. Exits on a transport failure or an error reply.
+pub async fn query(body: MessageBody) -> MessageBody {
+    let (mut reader, mut writer) = connect().await;
+
+    let msg = Message {
+        id: 1,
+        sender: "rev-cli".to_string(),
+        auth_token: None,
+        body,
+    };
+    if let Err(e) = protocol::send_message(&mut writer, &msg).await {
+        eprintln!("rev: failed to send query: {}", e);
+        std::process::exit(1);
+    }
+
+    match protocol::recv_message(&mut reader).await {
+        Ok(response) => match response.body {
+            MessageBody::Error { message } => {
+                eprintln!("rev: {}", message);
+                std::process::exit(1);
+            }
+            body => body,
+        },
+        Err(e) => {
+            eprintln!("rev: failed to read response: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! `rev timers`: the next and last run of every scheduled service, or with a
+//! service name, that service's recorded timer-started runs.
+
+use crate::bus::protocol::MessageBody;
+use chrono::{Local, TimeZone};
+
+pub(super) fn format_time(ts: Option<i64>) -> String {
+    ts.and_then(|t| Local.timestamp_opt(t, 0).single())
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_duration(secs: Option<u64>) -> String {
+    match secs {
+        None => "-".to_string(),
+        Some(s) if s >= 3600 => format!("{}h {}m", s / 3600, (s % 3600) / 60),
+        Some(s) if s >= 60 => format!("{}m {}s", s / 60, s % 60),
+        Some(s) => format!("{}s", s),
+    }
+}
+
+pub async fn run(service: Option<&str>) {
+    if let Some(service) = service {
+        return history(service).await;
+    }
+    let timers = match super::service_client::query(MessageBody::ListTimers).await {
+        MessageBody::TimerList { timers } => timers,
+        _ => {
+            eprintln!("rev: unexpected response");
+            std::process::exit(1);
+        }
+    };
+    if timers.is_empty() {
+        println!("No timers.");
+        return;
+    }
+
+    println!(
+        "{:<20}  {:<20}  {:<24}  {:<32}  TIMER",
+        "NEXT", "LAST", "RESULT", "SERVICE"
+    );
+    let mut timers = timers;
+    // Soonest first; timers with nothing pending go last.
+    timers.sort_by_key(|t| (t.next_run.is_none(), t.next_run));
+    for t in timers {
+        println!(
+            "{:<20}  {:<20}  {:<24}  {:<32}  {}",
+            format_time(t.next_run),
+            format_time(t.last_run),
+            t.last_result.as_deref().unwrap_or("-"),
+            t.service,
+            t.timer
+        );
+    }
+}
+
+async fn history(service: &str) {
+    let request = MessageBody::TimerHistory {
+        service: service.to_string(),
+    };
+    let runs = match super::service_client::query(request).await {
+        MessageBody::TimerRuns { runs } => runs,
+        _ => {
+            eprintln!("rev: unexpected response");
+            std::process::exit(1);
+        }
+    };
+    if runs.is_empty() {
+        println!("No recorded timer runs for {}.", service);
+        return;
+    }
+    println!("{:<20}  {:<10}  RESULT", "STARTED", "DURATION");
+    for run in runs {
+        println!(
+            "{:<20}  {:<10}  {}",
+            format_time(Some(run.started)),
+            format_duration(run.duration_secs),
+            run.result
+        );
+    }
+}
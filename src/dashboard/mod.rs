@@ -9,7 +9,7 @@ use ratatui::{
 };
 use tokio::net::UnixStream;
 
-use crate::bus::protocol::{self, Message, MessageBody};
+use crate::bus::protocol::{self, Message, MessageBody, TimerEntry};
 
 #[derive(Debug, Clone)]
 struct ServiceEntry {
@@ -24,9 +24,13 @@ struct ServiceEntry {
     cpu: String,
     tasks: String,
     restart_count: u32,
+    /// Health-check verdict of the current run, if the service has a check.
+    health: Option<String>,
     exit_code: Option<i32>,
     config_path: String,
     log_tail: Vec<String>,
+    /// Schedule and last timer-started run, for scheduled services.
+    timer: Option<TimerEntry>,
 }
 
 enum View {
@@ -61,6 +65,12 @@ impl App {
                     .into_iter()
                     .map(|(name, info)| build_entry(name, info))
                     .collect();
+                // Timers are extra detail; a failure here leaves them blank.
+                if let Ok(timers) = fetch_timers().await {
+                    for svc in &mut self.services {
+                        svc.timer = timers.iter().find(|t| t.service == svc.name).cloned();
+                    }
+                }
                 self.status_msg = format!("Loaded {} services", self.services.len());
             }
             Err(e) => {
@@ -100,9 +110,11 @@ impl App {
                                 cpu: "—".into(),
                                 tasks: "—".into(),
                                 restart_count: 0,
+                                health: None,
                                 exit_code: None,
                                 config_path: p.display().to_string(),
                                 log_tail,
+                                timer: None,
                             });
                         }
                     }
@@ -215,9 +227,11 @@ fn build_entry(name: String, info: crate::bus::protocol::ServiceSnapshot) -> Ser
         cpu,
         tasks,
         restart_count: info.restart_count,
+        health: info.health,
         exit_code: info.last_exit_code,
         config_path: info.config_path.unwrap_or("—".into()),
         log_tail,
+        timer: None,
     }
 }
 
@@ -250,6 +264,41 @@ async fn fetch_services()
     }
 }
 
+async fn fetch_timers() -> std::result::Result<Vec<TimerEntry>, String> {
+    let socket_path = crate::bus::socket_path();
+    let stream = UnixStream::connect(&socket_path)
+        .await
+        .map_err(|e| format!("{}", e))?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    let msg = Message {
+        id: 1,
+        sender: "rev-dashboard".to_string(),
+        auth_token: None,
+        body: MessageBody::ListTimers,
+    };
+    protocol::send_message(&mut writer, &msg)
+        .await
+        .map_err(|e| format!("{}", e))?;
+
+    let response = protocol::recv_message(&mut reader)
+        .await
+        .map_err(|e| format!("{}", e))?;
+
+    match response.body {
+        MessageBody::TimerList { timers } => Ok(timers),
+        MessageBody::Error { message } => Err(message),
+        _ => Err("unexpected response".to_string()),
+    }
+}
+
+fn format_timestamp(ts: Option<i64>) -> String {
+    use chrono::TimeZone;
+    ts.and_then(|t| chrono::Local.timestamp_opt(t, 0).single())
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "—".to_string())
+}
+
 async fn send_bus_command(body: MessageBody) -> std::result::Result<String, String> {
     let socket_path = crate::bus::socket_path();
     let stream = UnixStream::connect(&socket_path)
@@ -274,6 +323,11 @@ async fn send_bus_command(body: MessageBody) -> std::result::Result<String, Stri
     match response.body {
         MessageBody::Ok { message } => Ok(message),
         MessageBody::Error { message } => Err(message),
+        MessageBody::JobQueued {
+            job_id,
+            service,
+            kind,
+        } => Ok(format!("Queued job {}: {} {}", job_id, kind, service)),
         _ => Ok("done".to_string()),
     }
 }
@@ -522,6 +576,20 @@ fn render_detail(app: &App, index: usize, frame: &mut Frame, area: Rect) {
         ]));
     }
 
+    if let Some(health) = &svc.health {
+        lines.push(Line::from(vec![
+            Span::styled("   Health: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                health.as_str(),
+                Style::default().fg(if health == "healthy" {
+                    Color::Green
+                } else {
+                    Color::Red
+                }),
+            ),
+        ]));
+    }
+
     lines.push(Line::from(vec![
         Span::styled("    Tasks: ", Style::default().fg(Color::DarkGray)),
         Span::raw(&svc.tasks),
@@ -556,6 +624,28 @@ fn render_detail(app: &App, index: usize, frame: &mut Frame, area: Rect) {
         ]));
     }
 
+    if let Some(timer) = &svc.timer {
+        lines.push(Line::from(vec![
+            Span::styled("    Timer: ", Style::default().fg(Color::DarkGray)),
+            Span::raw(format!(
+                "{}; next {}",
+                timer.timer,
+                format_timestamp(timer.next_run)
+            )),
+        ]));
+        let result = timer.last_result.as_deref().unwrap_or("—");
+        let result_color = match result {
+            "success" | "running" => Color::Green,
+            "—" => Color::Gray,
+            _ => Color::Red,
+        };
+        lines.push(Line::from(vec![
+            Span::styled(" Last run: ", Style::default().fg(Color::DarkGray)),
+            Span::raw(format!("{} ", format_timestamp(timer.last_run))),
+            Span::styled(result, Style::default().fg(result_color)),
+        ]));
+    }
+
     lines.push(Line::from(vec![
         Span::styled("     Exec: ", Style::default().fg(Color::DarkGray)),
         Span::raw(&svc.exec),
@@ -13,7 +13,6 @@ use std::os::fd::AsRawFd;
 use std::thread;
 use std::time::Duration;
 
-const SHELL: &str = "/Core/Bin/brush";
 const OOBE: &str = "/Core/Bin/oobe";
 const PASSWD: &str = "/Vault/Accounts/passwd";
 
@@ -80,16 +79,11 @@ fn take_console() {
     }
 }
 
-/// Run a forked child that owns the console and execs `prog`, waiting (via
-/// kill-poll so it does not fight the zombie reaper) until it exits.
+/// Run a forked child that owns the console and execs `prog`, waiting until it
+/// exits.
 fn run_on_console_blocking(prog: &str) {
-    match unsafe { nix::unistd::fork() } {
-        Ok(nix::unistd::ForkResult::Parent { child, .. }) => loop {
-            thread::sleep(Duration::from_millis(500));
-            if nix::sys::signal::kill(child, None).is_err() {
-                break;
-            }
-        },
+    match crate::service::supervisor::fork_and_wait() {
+        Ok(nix::unistd::ForkResult::Parent { .. }) => {}
         Ok(nix::unistd::ForkResult::Child) => {
             take_console();
             let p = CString::new(prog).unwrap();
@@ -109,18 +103,13 @@ pub fn spawn_console() {
             run_on_console_blocking(OOBE);
         }
         loop {
-            match unsafe { nix::unistd::fork() } {
-                Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
-                    loop {
-                        thread::sleep(Duration::from_millis(800));
-                        if nix::sys::signal::kill(child, None).is_err() {
-                            break;
-                        }
-                    }
+            let settings = crate::settings::get();
+            match crate::service::supervisor::fork_and_wait() {
+                Ok(nix::unistd::ForkResult::Parent { .. }) => {
                     thread::sleep(Duration::from_millis(300));
                 }
                 Ok(nix::unistd::ForkResult::Child) => {
-                    login_child();
+                    login_child(&settings);
                     std::process::exit(127);
                 }
                 Err(e) => {
@@ -135,13 +124,13 @@ pub fn spawn_console() {
 /// Child: own the console, then log in as the first human account (dropping to
 /// its uid/gid) and exec its shell. Falls back to a root shell if no account is
 /// present (e.g. OOBE was skipped).
-fn login_child() {
+fn login_child(settings: &crate::settings::Settings) {
     take_console();
 
     let user = first_user();
     let (name, uid, gid, home, shell) = match &user {
         Some(u) => (u.name.as_str(), u.uid, u.gid, u.home.as_str(), u.shell.as_str()),
-        None => ("root", 0, 0, "/", SHELL),
+        None => ("root", 0, 0, "/", settings.console_shell.as_str()),
     };
 
     // Drop privileges: supplementary groups, gid, then uid (uid last). Done in
@@ -157,7 +146,7 @@ fn login_child() {
 
     unsafe {
         std::env::set_var("HOME", home);
-        std::env::set_var("PATH", "/Core/Bin");
+        std::env::set_var("PATH", &settings.console_path);
         std::env::set_var("USER", name);
         std::env::set_var("LOGNAME", name);
         std::env::set_var("SHELL", shell);
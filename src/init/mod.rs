@@ -1,7 +1,9 @@
 pub mod console;
 pub mod mounts;
 pub mod ordering;
+pub mod reload;
 pub mod services;
+pub mod watch;
 
 /// Mount the config overlay before anything else.
 fn mount_config_overlay() {
@@ -50,6 +52,11 @@ async fn shutdown() {
         let _ = crate::session::end_session(session.session_id);
     }
 
+    // Stop user services, lingering ones included, with their lanes.
+    for (uid, _) in crate::bus::lanes::LANES.list_lanes() {
+        let _ = crate::bus::lanes::LANES.stop_lane(uid);
+    }
+
     // Stop services in reverse order (last started = first stopped)
     let all_services = services::list_services();
     for (_name, info) in all_services.iter().rev() {
@@ -68,13 +75,22 @@ async fn shutdown() {
 
 pub async fn run(auto_start: bool) {
     // Production (real PID 1): mount the kernel pseudo-filesystems first, then
-    // the config overlay. Skipped in debug so a dev run never touches host mounts.
+    // the config overlay, and take over core dumps. Skipped in debug so a dev
+    // run never touches host mounts or the host's core_pattern.
     if !cfg!(debug_assertions) {
         mounts::early_mounts();
         mount_config_overlay();
+        crate::service::cgroup::setup();
+        crate::service::coredump::install();
     }
 
-    crate::service::reap_zombies_loop();
+    // rev's own settings, once the overlay that may hold rev.toml is mounted.
+    if let Err(e) = crate::settings::reload() {
+        eprintln!("rev: {}; using defaults", e);
+    }
+
+    crate::service::supervisor::start();
+    services::log_transitions();
 
     let directories = crate::parser::service_dirs();
 
@@ -120,7 +136,7 @@ pub async fn run(auto_start: bool) {
 
         // Only system-scope services start at boot on the Highway. User-scope
         // services start per-user on a Lane at login, so they are deferred here.
-        let (system, user): (Vec<_>, Vec<_>) = candidates
+        let (mut system, user): (Vec<_>, Vec<_>) = candidates
             .into_iter()
             .partition(|(_, cfg, _)| cfg.scope == crate::parser::ServiceScope::System);
         if !user.is_empty() {
@@ -130,6 +146,24 @@ pub async fn run(auto_start: bool) {
             );
         }
 
+        // A service whose on-success or on-failure names nothing loadable is
+        // refused now, not when the trigger fires.
+        let names: std::collections::HashSet<String> =
+            system.iter().map(|(name, _, _)| name.clone()).collect();
+        system.retain(|(_, config, path)| {
+            match crate::service::unresolved_trigger(config, |t| names.contains(t)) {
+                Some(target) => {
+                    eprintln!(
+                        "rev: {}: trigger target '{}' is not loaded, skipping",
+                        path.display(),
+                        target
+                    );
+                    false
+                }
+                None => true,
+            }
+        });
+
         let sortable: Vec<(String, crate::parser::ServiceConfig)> = system
             .iter()
             .map(|(name, config, _)| (name.clone(), config.clone()))
@@ -142,13 +176,66 @@ pub async fn run(auto_start: bool) {
             );
         }
         for idx in order {
-            crate::service::start_service_from_path(&system[idx].2);
+            let (name, config, path) = &system[idx];
+            // Templates only run as instances, started by a trigger.
+            if config.is_template() {
+                services::register_service(
+                    name.clone(),
+                    crate::parser::ServiceInfo {
+                        name: name.clone(),
+                        config_path: Some(path.display().to_string()),
+                        config: config.clone(),
+                        ..Default::default()
+                    },
+                );
+                continue;
+            }
+            // Path- and timer-triggered services wait for their condition;
+            // the path watcher or the scheduler starts them.
+            if config.is_path_triggered() || config.is_timer_triggered() {
+                services::register_service(
+                    name.clone(),
+                    crate::parser::ServiceInfo {
+                        name: name.clone(),
+                        config_path: Some(path.display().to_string()),
+                        config: config.clone(),
+                        ..Default::default()
+                    },
+                );
+                services::set_state(
+                    name,
+                    crate::parser::ServiceState::ConditionSkipped,
+                    "waiting for its path or timer condition",
+                );
+                continue;
+            }
+            // A service waiting on bus names cannot hold up boot: the Highway
+            // they register on only comes up once every boot service launched.
+            if !config.requires_bus_name.is_empty() {
+                let path = path.clone();
+                std::thread::spawn(move || crate::service::start_service_from_path(&path));
+                continue;
+            }
+            crate::service::start_service_from_path(path);
         }
     }
 
-    // Start the cron scheduler
+    // Start the cron and monotonic timer scheduler
     crate::service::scheduler::start_scheduler();
 
+    // Start services whose watched paths change or appear.
+    crate::service::paths::start_path_watcher();
+
+    // Reload automatically when .rsc files are added, changed, or removed.
+    watch::start();
+
+    // Lingering users' lanes and services run without a login.
+    crate::session::linger::start_all();
+
+    // Every boot service has been launched and timers and path triggers are
+    // armed.
+    crate::bus::signals::reach_milestone("services-started");
+
     // Bring up an interactive console (dev login; real per-user login lands with
     // the greeter + Rook Guard flow). Skipped in debug.
     if !cfg!(debug_assertions) {
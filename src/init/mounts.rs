@@ -25,7 +25,7 @@ fn already_mounted(target: &str) -> bool {
     false
 }
 
-/// Mount /proc, /sys, /dev (+pts, shm), /run and /tmp. Best-effort: a mount that
+/// Mount /proc, /sys, /sys/fs/cgroup, /dev (+pts, shm), /run and /tmp. Best-effort: a mount that
 /// is already present (e.g. devtmpfs auto-mounted by the kernel) is skipped, and
 /// individual failures are logged but do not abort boot.
 pub fn early_mounts() {
@@ -47,6 +47,14 @@ pub fn early_mounts() {
             flags: nodev_noexec_nosuid,
             data: None,
         },
+        // Unified cgroup hierarchy, for per-service cgroups.
+        Pfs {
+            source: "cgroup2",
+            target: "/sys/fs/cgroup",
+            fstype: "cgroup2",
+            flags: nodev_noexec_nosuid,
+            data: None,
+        },
         Pfs {
             source: "devtmpfs",
             target: "/dev",
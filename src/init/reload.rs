@@ -0,0 +1,308 @@
+//! Daemon-reload: re-read every .rsc file and reconcile the service table.
+//!
+//! Boot registers whatever it finds once; after that the files can change under
+//! rev. [`reload`] walks the service directories again, keyed by each file's
+//! `name` (never its file stem, which need not match), and diffs the result
+//! against the in-memory configs:
+//!
+//!   * a name rev has never seen is registered (not started, as at boot only the
+//!     boot sequence starts services);
+//!   * a changed definition replaces the old one in place, keeping runtime
+//!     state. A running service keeps its old process, so it is reported as
+//!     stale until it is restarted;
+//!   * a name whose file is gone is deregistered. A running one is stopped first
+//!     when it opted in with `stop-on-remove`; otherwise it keeps running and is
+//!     dropped from the table when it exits.
+//!
+//! Only system-scope services are reconciled here. User-scope services belong to
+//! the per-user lanes, exactly as at boot.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use super::services;
+use crate::parser::{ServiceConfig, ServiceInfo, ServiceScope};
+
+/// What a reload did, for the bus reply and the log.
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    /// Running services whose definition changed (or whose file is gone) and
+    /// that need a restart to pick it up.
+    pub stale: Vec<String>,
+    /// Files that could not be read, parsed or loaded, with the reason.
+    pub errors: Vec<String>,
+}
+
+impl ReloadReport {
+    /// One-line summary, naming the services that need a restart.
+    pub fn summary(&self) -> String {
+        let mut out = format!(
+            "Reloaded: {} added, {} changed, {} removed",
+            self.added.len(),
+            self.changed.len(),
+            self.removed.len()
+        );
+        if !self.stale.is_empty() {
+            out.push_str(&format!("; restart needed: {}", self.stale.join(", ")));
+        }
+        if !self.errors.is_empty() {
+            out.push_str(&format!("; {} file(s) failed to load", self.errors.len()));
+        }
+        out
+    }
+}
+
+/// The differences between the table and the files on disk.
+#[derive(Debug, Default)]
+struct Plan {
+    added: Vec<(ServiceConfig, PathBuf)>,
+    /// Definitions that differ from the table, or moved to another file.
+    changed: Vec<(ServiceConfig, PathBuf)>,
+    removed: Vec<String>,
+}
+
+/// Walk `dirs` for parseable system-scope services. The first file to claim a
+/// name wins, matching boot, where a later duplicate is refused as already
+/// registered.
+fn scan(dirs: &[PathBuf], errors: &mut Vec<String>) -> Vec<(ServiceConfig, PathBuf)> {
+    let mut found: Vec<(ServiceConfig, PathBuf)> = Vec::new();
+    let mut seen = HashSet::new();
+    for dir in dirs {
+        if !dir.exists() {
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(dir).into_iter().flatten() {
+            let path = entry.path();
+            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("rsc") {
+                continue;
+            }
+            let parsed = std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|t| {
+                    crate::parser::deserialize_service_config(&t).map_err(|e| e.to_string())
+                });
+            let config = match parsed {
+                Ok(c) => c,
+                Err(e) => {
+                    errors.push(format!("{}: {}", path.display(), e));
+                    continue;
+                }
+            };
+            if config.scope != ServiceScope::System {
+                continue;
+            }
+            if !seen.insert(config.name.clone()) {
+                eprintln!(
+                    "rev: reload: {} redefines {}, keeping the first definition",
+                    path.display(),
+                    config.name
+                );
+                continue;
+            }
+            found.push((config, path.to_path_buf()));
+        }
+    }
+    found
+}
+
+/// Refuse the services whose `on-success` or `on-failure` target is not among
+/// `found`, as boot does, recording each in `errors`.
+fn check_triggers(
+    found: Vec<(ServiceConfig, PathBuf)>,
+    errors: &mut Vec<String>,
+) -> Vec<(ServiceConfig, PathBuf)> {
+    let names: HashSet<String> = found.iter().map(|(c, _)| c.name.clone()).collect();
+    found
+        .into_iter()
+        .filter(|(config, path)| {
+            let unresolved = crate::service::unresolved_trigger(config, |t| names.contains(t));
+            if let Some(target) = &unresolved {
+                errors.push(format!(
+                    "{}: trigger target '{}' is not loaded",
+                    path.display(),
+                    target
+                ));
+            }
+            unresolved.is_none()
+        })
+        .collect()
+}
+
+/// Diff the registered services against what is on disk. Only file-backed
+/// entries (those with a `config_path`) can be removed by a reload.
+fn plan(current: &[(String, ServiceInfo)], found: Vec<(ServiceConfig, PathBuf)>) -> Plan {
+    let known: HashMap<&str, &ServiceInfo> =
+        current.iter().map(|(name, info)| (name.as_str(), info)).collect();
+    let on_disk: HashSet<String> = found.iter().map(|(c, _)| c.name.clone()).collect();
+
+    let mut plan = Plan::default();
+    for (config, path) in found {
+        match known.get(config.name.as_str()) {
+            None => plan.added.push((config, path)),
+            // A transient service holds its name until it exits.
+            Some(info) if info.transient => {}
+            Some(info) => {
+                let moved = info.config_path.as_deref().map(Path::new) != Some(path.as_path());
+                if info.config != config || moved {
+                    plan.changed.push((config, path));
+                }
+            }
+        }
+    }
+    for (name, info) in current {
+        if info.config_path.is_some() && !on_disk.contains(name) {
+            plan.removed.push(name.clone());
+        }
+    }
+    plan
+}
+
+/// Re-read rev.toml and the service directories, and apply the differences to
+/// the table.
+pub fn reload() -> ReloadReport {
+    let mut report = ReloadReport::default();
+    if let Err(e) = crate::settings::reload() {
+        report.errors.push(e);
+    }
+    let found = scan(&crate::parser::service_dirs(), &mut report.errors);
+    let found = check_triggers(found, &mut report.errors);
+    let plan = plan(&services::list_services(), found);
+
+    for (config, path) in plan.added {
+        let name = config.name.clone();
+        services::register_service(
+            name.clone(),
+            ServiceInfo {
+                name: name.clone(),
+                config_path: Some(path.display().to_string()),
+                config,
+                ..Default::default()
+            },
+        );
+        report.added.push(name);
+    }
+
+    for (config, path) in plan.changed {
+        let name = config.name.clone();
+        let running = services::get_service(&name).is_some_and(|i| i.is_running);
+        let new_config = config.clone();
+        if let Some(old) =
+            services::replace_config(&name, config, Some(path.display().to_string()))
+            && old != new_config
+        {
+            if running {
+                report.stale.push(name.clone());
+            }
+            report.changed.push(name);
+        }
+    }
+
+    for name in plan.removed {
+        let Some(info) = services::get_service(&name) else { continue };
+        if !info.is_running {
+            services::deregister_service(&name);
+        } else if info.config.stop_on_remove {
+            crate::service::stop_service(&info);
+            services::deregister_service(&name);
+        } else {
+            // Leave the process alone, but forget the file: with no config path
+            // it will not be restarted, and is dropped from the table on exit.
+            services::replace_config(&name, info.config.clone(), None);
+            report.stale.push(name.clone());
+        }
+        report.removed.push(name);
+    }
+
+    for e in &report.errors {
+        eprintln!("rev: reload: {}", e);
+    }
+    crate::logger::write_log("rev", &report.summary());
+    crate::service::paths::refresh();
+    crate::service::scheduler::wake();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(name: &str, exec: &str) -> ServiceConfig {
+        ServiceConfig {
+            name: name.to_string(),
+            exec_start: exec.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn info(config: ServiceConfig, path: &str) -> (String, ServiceInfo) {
+        (
+            config.name.clone(),
+            ServiceInfo {
+                name: config.name.clone(),
+                config_path: Some(path.to_string()),
+                config,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn diff_finds_added_changed_and_removed() {
+        let current = vec![
+            info(cfg("same", "/bin/a"), "/s/same.rsc"),
+            info(cfg("edited", "/bin/old"), "/s/edited.rsc"),
+            info(cfg("gone", "/bin/c"), "/s/gone.rsc"),
+        ];
+        let found = vec![
+            (cfg("same", "/bin/a"), PathBuf::from("/s/same.rsc")),
+            (cfg("edited", "/bin/new"), PathBuf::from("/s/edited.rsc")),
+            (cfg("fresh", "/bin/d"), PathBuf::from("/s/fresh.rsc")),
+        ];
+        let p = plan(&current, found);
+        let names = |v: &[(ServiceConfig, PathBuf)]| {
+            v.iter().map(|(c, _)| c.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&p.added), ["fresh"]);
+        assert_eq!(names(&p.changed), ["edited"]);
+        assert_eq!(p.removed, ["gone"]);
+    }
+
+    #[test]
+    fn services_are_matched_by_name_not_file_stem() {
+        // The file is called indexer.rsc but defines a differently named
+        // service; it must match the registered entry, not look new.
+        let worker = cfg("com.example.app/worker", "/bin/w");
+        let current = vec![info(worker.clone(), "/s/indexer.rsc")];
+        let found = vec![(worker, PathBuf::from("/s/indexer.rsc"))];
+        let p = plan(&current, found);
+        assert!(p.added.is_empty() && p.changed.is_empty() && p.removed.is_empty());
+    }
+
+    #[test]
+    fn entries_without_a_file_are_never_removed() {
+        let mut current = vec![info(cfg("adhoc", "/bin/x"), "/s/adhoc.rsc")];
+        current[0].1.config_path = None;
+        assert!(plan(&current, Vec::new()).removed.is_empty());
+    }
+
+    #[test]
+    fn scan_skips_user_scope_and_reports_bad_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut user = cfg("u", "/bin/u");
+        user.scope = ServiceScope::User;
+        for c in [cfg("s", "/bin/s"), user] {
+            let text = crate::parser::serialize_service_config(&c).unwrap();
+            std::fs::write(dir.path().join(format!("{}.rsc", c.name)), text).unwrap();
+        }
+        std::fs::write(dir.path().join("broken.rsc"), "not = [toml").unwrap();
+
+        let mut errors = Vec::new();
+        let found = scan(&[dir.path().to_path_buf()], &mut errors);
+        let names: Vec<&str> = found.iter().map(|(c, _)| c.name.as_str()).collect();
+        assert_eq!(names, ["s"]);
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -1,99 +1,634 @@
-use crate::parser::ServiceInfo;
+//! The service table, owned by a single actor thread.
+//!
+//! Every read and write of service state is a typed [`Command`] sent to one
+//! thread that owns the table outright, so there are no locks to take in the
+//! wrong order: the supervisor's exit watches, the bus, the job engine, the
+//! scheduler and activation are all just senders. The functions below wrap the
+//! commands and block only until the actor has answered.
+//!
+//! Each service moves through the [`ServiceState`] machine; the actor refuses
+//! transitions it does not allow and broadcasts every one it applies as a
+//! [`ServiceEvent`], together with services being added and removed. It also
+//! keeps the last [`RUN_HISTORY_LEN`] runs of every service as [`RunRecord`]s,
+//! so "why did it die, and how often?" survives the next restart.
+
+use crate::parser::{Health, ServiceConfig, ServiceInfo, ServiceState};
+use crate::service::supervisor::Exit;
+use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use std::sync::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc;
+use std::thread;
+use tokio::sync::broadcast;
+
+/// A change to the service table, broadcast to [`subscribe`]rs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceEvent {
+    Added {
+        service: String,
+    },
+    Removed {
+        service: String,
+    },
+    StateChanged {
+        service: String,
+        old: ServiceState,
+        new: ServiceState,
+        pid: Option<u32>,
+        exit_code: Option<i32>,
+        reason: String,
+    },
+}
+
+/// Runs kept per service.
+pub const RUN_HISTORY_LEN: usize = 16;
+
+/// One run of a service: a start attempt and, once it ended, how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunRecord {
+    pub started: DateTime<Utc>,
+    /// `None` while the run is still going.
+    pub ended: Option<DateTime<Utc>>,
+    /// `None` if the start failed before a process was forked.
+    pub pid: Option<u32>,
+    /// How the process ended; `None` while running or if it never started.
+    pub exit: Option<Exit>,
+    /// Set when the restart policy started this run, saying why.
+    pub restart_reason: Option<String>,
+    /// Hooks that failed during this run, e.g. `exec-start-pre: exited 1`.
+    pub hook_failures: Vec<String>,
+    /// Whether the kernel's OOM killer ended the run.
+    pub oom_killed: bool,
+}
 
+type Reply<T> = mpsc::SyncSender<T>;
+
+enum Command {
+    Register(String, Box<ServiceInfo>),
+    RegisterNew(String, Box<ServiceInfo>, Reply<bool>),
+    Deregister(String),
+    Get(String, Reply<Option<ServiceInfo>>),
+    GetByPid(u32, Reply<Option<ServiceInfo>>),
+    List(Reply<Vec<(String, ServiceInfo)>>),
+    ReplaceConfig(
+        String,
+        Box<ServiceConfig>,
+        Option<String>,
+        Reply<Option<ServiceConfig>>,
+    ),
+    SetState(String, ServiceState, String, Reply<bool>),
+    BeginRun(String, Option<String>, Reply<bool>),
+    Started(String, u32),
+    Exited(u32, Exit, bool, Reply<Option<ServiceInfo>>),
+    HookFailed(String, String),
+    History(String, Reply<Vec<RunRecord>>),
+    IncrementRestartCount(String),
+    SetHealth(u32, Health),
+}
+
+/// The table the actor owns: services by name, running PIDs to names, and
+/// each service's recent runs (kept after it is deregistered).
 #[derive(Default)]
-struct ServicesStatus {
+struct Table {
     services: HashMap<String, ServiceInfo>,
+    running: HashMap<u32, String>,
+    history: HashMap<String, VecDeque<RunRecord>>,
 }
 
-static SERVICES: Lazy<Mutex<ServicesStatus>> = Lazy::new(|| {
-    Mutex::new(ServicesStatus {
-        services: HashMap::new(),
-    })
+/// Events are dropped for subscribers that fall this far behind.
+const EVENT_BACKLOG: usize = 256;
+
+static EVENTS: Lazy<broadcast::Sender<ServiceEvent>> =
+    Lazy::new(|| broadcast::channel(EVENT_BACKLOG).0);
+
+static ACTOR: Lazy<mpsc::Sender<Command>> = Lazy::new(|| {
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("service-table".to_string())
+        .spawn(move || {
+            let mut table = Table::default();
+            for command in rx {
+                table.handle(command);
+            }
+        })
+        .expect("failed to spawn the service table");
+    tx
 });
 
-/// Maps PID -> service name for running processes.
-static RUNNING_PROCESSES: Lazy<Mutex<HashMap<u32, String>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+fn send(command: Command) {
+    ACTOR
+        .send(command)
+        .expect("service table actor has stopped");
+}
+
+fn ask<T>(command: impl FnOnce(Reply<T>) -> Command) -> T {
+    let (tx, rx) = mpsc::sync_channel(1);
+    send(command(tx));
+    rx.recv().expect("service table actor has stopped")
+}
+
+fn emit(event: ServiceEvent) {
+    // No receivers is not an error: nobody is listening yet.
+    let _ = EVENTS.send(event);
+}
+
+impl Table {
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Register(name, info) => {
+                if let Some(pid) = info.pid.filter(|_| info.is_running) {
+                    self.running.insert(pid, name.clone());
+                }
+                if self.services.insert(name.clone(), *info).is_none() {
+                    emit(ServiceEvent::Added { service: name });
+                }
+            }
+            Command::RegisterNew(name, info, reply) => {
+                let added = !self.services.contains_key(&name);
+                if added {
+                    if let Some(pid) = info.pid.filter(|_| info.is_running) {
+                        self.running.insert(pid, name.clone());
+                    }
+                    self.services.insert(name.clone(), *info);
+                    emit(ServiceEvent::Added { service: name });
+                }
+                let _ = reply.send(added);
+            }
+            Command::Deregister(name) => {
+                if self.services.remove(&name).is_some() {
+                    self.running.retain(|_, n| *n != name);
+                    emit(ServiceEvent::Removed { service: name });
+                }
+            }
+            Command::Get(name, reply) => {
+                let _ = reply.send(self.services.get(&name).cloned());
+            }
+            Command::GetByPid(pid, reply) => {
+                let info = self
+                    .running
+                    .get(&pid)
+                    .and_then(|name| self.services.get(name))
+                    .cloned();
+                let _ = reply.send(info);
+            }
+            Command::List(reply) => {
+                let list = self
+                    .services
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                let _ = reply.send(list);
+            }
+            Command::ReplaceConfig(name, config, config_path, reply) => {
+                let old = self.services.get_mut(&name).map(|info| {
+                    info.config_path = config_path;
+                    std::mem::replace(&mut info.config, *config)
+                });
+                let _ = reply.send(old);
+            }
+            Command::SetState(name, state, reason, reply) => {
+                let applied = self.transition(&name, state, None, reason);
+                // A start that failed before forking ends its run here.
+                if applied
+                    && state == ServiceState::Failed
+                    && let Some(run) = self.open_run(&name)
+                {
+                    run.ended = Some(Utc::now());
+                }
+                let _ = reply.send(applied);
+            }
+            Command::BeginRun(name, restart_reason, reply) => {
+                let reason = match restart_reason {
+                    Some(ref r) => format!("restart: {}", r),
+                    None => "start requested".to_string(),
+                };
+                let applied = self.transition(&name, ServiceState::Starting, None, reason);
+                if applied {
+                    let runs = self.history.entry(name).or_default();
+                    if runs.len() == RUN_HISTORY_LEN {
+                        runs.pop_front();
+                    }
+                    runs.push_back(RunRecord {
+                        started: Utc::now(),
+                        ended: None,
+                        pid: None,
+                        exit: None,
+                        restart_reason,
+                        hook_failures: Vec::new(),
+                        oom_killed: false,
+                    });
+                }
+                let _ = reply.send(applied);
+            }
+            Command::Started(name, pid) => {
+                if self.transition(&name, ServiceState::Running, Some(pid), "process started")
+                    && let Some(info) = self.services.get_mut(&name)
+                {
+                    info.pid = Some(pid);
+                    info.up_timestamp = Some(Utc::now());
+                    info.health = None;
+                    self.running.insert(pid, name.clone());
+                    if let Some(run) = self.open_run(&name) {
+                        run.pid = Some(pid);
+                    }
+                }
+            }
+            Command::HookFailed(name, failure) => {
+                if let Some(run) = self.history.get_mut(&name).and_then(|r| r.back_mut()) {
+                    run.hook_failures.push(failure);
+                }
+            }
+            Command::History(name, reply) => {
+                let runs = self
+                    .history
+                    .get(&name)
+                    .map(|r| r.iter().cloned().collect())
+                    .unwrap_or_default();
+                let _ = reply.send(runs);
+            }
+            Command::Exited(pid, exit, oom_killed, reply) => {
+                let exit_code = exit.code();
+                let Some(name) = self.running.remove(&pid) else {
+                    let _ = reply.send(None);
+                    return;
+                };
+                let before = self.services.get(&name).cloned();
+                if let Some(info) = self.services.get_mut(&name) {
+                    info.pid = None;
+                    info.up_timestamp = None;
+                    info.health = None;
+                    info.last_exit_code = exit_code;
+                    // A requested stop ends cleanly however the process died;
+                    // otherwise only a zero exit does.
+                    let stopping = info.state == ServiceState::Stopping;
+                    let (state, reason) = match exit_code {
+                        Some(0) => (ServiceState::Inactive, "exited".to_string()),
+                        _ if stopping => (ServiceState::Inactive, "stopped".to_string()),
+                        _ if oom_killed => (ServiceState::Failed, "oom-kill".to_string()),
+                        _ => (ServiceState::Failed, exit.describe()),
+                    };
+                    self.transition_exited(&name, state, pid, exit_code, reason);
+                }
+                if let Some(run) = self.open_run(&name).filter(|r| r.pid == Some(pid)) {
+                    run.ended = Some(Utc::now());
+                    run.exit = Some(exit);
+                    run.oom_killed = oom_killed;
+                }
+                let _ = reply.send(before);
+            }
+            Command::IncrementRestartCount(name) => {
+                if let Some(info) = self.services.get_mut(&name) {
+                    info.restart_count += 1;
+                }
+            }
+            Command::SetHealth(pid, health) => {
+                if let Some(name) = self.running.get(&pid)
+                    && let Some(info) = self.services.get_mut(name)
+                {
+                    info.health = Some(health);
+                }
+            }
+        }
+    }
+
+    /// The run of `name` that has not ended yet, if any.
+    fn open_run(&mut self, name: &str) -> Option<&mut RunRecord> {
+        self.history
+            .get_mut(name)?
+            .back_mut()
+            .filter(|r| r.ended.is_none())
+    }
+
+    /// Move `name` to `state` if the state machine allows it, broadcasting the
+    /// change. Returns whether it was applied.
+    fn transition(
+        &mut self,
+        name: &str,
+        state: ServiceState,
+        pid: Option<u32>,
+        reason: impl Into<String>,
+    ) -> bool {
+        let Some(info) = self.services.get_mut(name) else {
+            return false;
+        };
+        let old = info.state;
+        if !old.can_become(state) {
+            eprintln!(
+                "rev: {}: ignoring transition {} -> {}",
+                name,
+                old.as_str(),
+                state.as_str()
+            );
+            return false;
+        }
+        info.state = state;
+        info.is_running = state.has_process();
+        emit(ServiceEvent::StateChanged {
+            service: name.to_string(),
+            old,
+            new: state,
+            pid: pid.or(info.pid),
+            exit_code: None,
+            reason: reason.into(),
+        });
+        true
+    }
+
+    /// An exit always takes effect, whatever state the service was in.
+    fn transition_exited(
+        &mut self,
+        name: &str,
+        state: ServiceState,
+        pid: u32,
+        exit_code: Option<i32>,
+        reason: String,
+    ) {
+        let Some(info) = self.services.get_mut(name) else {
+            return;
+        };
+        let old = info.state;
+        info.state = state;
+        info.is_running = false;
+        emit(ServiceEvent::StateChanged {
+            service: name.to_string(),
+            old,
+            new: state,
+            pid: Some(pid),
+            exit_code,
+            reason,
+        });
+    }
+}
 
 /// Register a service from its config.
 pub fn register_service(name: String, info: ServiceInfo) {
-    let mut status = SERVICES.lock().expect("services lock poisoned");
-    status.services.insert(name, info);
+    send(Command::Register(name, Box::new(info)));
+}
+
+/// Register a service unless the name is taken. Returns whether it was added.
+pub fn register_new(name: String, info: ServiceInfo) -> bool {
+    ask(|r| Command::RegisterNew(name, Box::new(info), r))
 }
 
 pub fn get_service(name: &str) -> Option<ServiceInfo> {
-    let status = SERVICES.lock().expect("services lock poisoned");
-    status.services.get(name).cloned()
+    ask(|r| Command::Get(name.to_string(), r))
 }
 
-/// Look up a service by its running PID.
+/// The service whose main process is `pid`, if any.
 pub fn get_service_by_pid(pid: u32) -> Option<ServiceInfo> {
-    let running = RUNNING_PROCESSES.lock().expect("running_processes lock poisoned");
-    let name = running.get(&pid)?;
-    let status = SERVICES.lock().expect("services lock poisoned");
-    status.services.get(name).cloned()
+    ask(|r| Command::GetByPid(pid, r))
 }
 
 pub fn list_services() -> Vec<(String, ServiceInfo)> {
-    let status = SERVICES.lock().expect("services lock poisoned");
-    status
-        .services
-        .iter()
-        .map(|(k, v)| (k.clone(), v.clone()))
-        .collect()
-}
-
-/// Update service status when starting or stopping.
-/// - `name` + `new_pid` = starting a service
-/// - `old_pid` only = a process exited (zombie reaped)
-pub fn update_service_pid(name: Option<&str>, new_pid: Option<i32>, old_pid: Option<i32>) {
-    let mut status = SERVICES.lock().expect("services lock poisoned");
-    let mut running = RUNNING_PROCESSES.lock().expect("running_processes lock poisoned");
-
-    if let Some(service_name) = name {
-        if let Some(info) = status.services.get_mut(service_name) {
-            if let Some(pid) = new_pid {
-                info.is_running = true;
-                info.pid = Some(pid as u32);
-                info.up_timestamp = Some(chrono::Utc::now());
-                running.insert(pid as u32, service_name.to_string());
-            } else if let Some(old) = old_pid {
-                info.is_running = false;
-                info.pid = None;
-                info.up_timestamp = None;
-                running.remove(&(old as u32));
-            }
-        }
-    }
+    ask(Command::List)
 }
 
-/// Mark a service as exited by its PID. Called from zombie reaper.
-pub fn mark_service_exited(pid: u32, exit_code: Option<i32>) {
-    let mut running = RUNNING_PROCESSES.lock().expect("running_processes lock poisoned");
-    let mut status = SERVICES.lock().expect("services lock poisoned");
+/// Move a service to `state`, e.g. `Starting` before its start hooks run or
+/// `Stopping` when a stop is requested. Returns false if the service is unknown
+/// or the state machine does not allow the transition.
+pub fn set_state(name: &str, state: ServiceState, reason: &str) -> bool {
+    ask(|r| Command::SetState(name.to_string(), state, reason.to_string(), r))
+}
 
-    if let Some(service_name) = running.remove(&pid) {
-        if let Some(info) = status.services.get_mut(&service_name) {
-            info.is_running = false;
-            info.pid = None;
-            info.last_exit_code = exit_code;
-            info.up_timestamp = None;
-        }
-    }
+/// Claim a start of `name` (it moves to `Starting`) and open a new run record.
+/// `restart_reason` is set when the restart policy is starting it. Returns
+/// false if the service is unknown or already starting or running.
+pub fn begin_run(name: &str, restart_reason: Option<&str>) -> bool {
+    ask(|r| Command::BeginRun(name.to_string(), restart_reason.map(str::to_string), r))
+}
+
+/// Note a failed hook against the service's current (or last) run.
+pub fn record_hook_failure(name: &str, failure: String) {
+    send(Command::HookFailed(name.to_string(), failure));
+}
+
+/// The recorded runs of `name`, oldest first.
+pub fn run_history(name: &str) -> Vec<RunRecord> {
+    ask(|r| Command::History(name.to_string(), r))
+}
+
+/// Record that a service's process was forked: it is now running as `pid`.
+pub fn mark_service_started(name: &str, pid: u32) {
+    send(Command::Started(name.to_string(), pid));
+}
+
+/// Record a service process's exit; `oom_killed` when the kernel's OOM killer
+/// ended it. Returns the service as it was just before the exit (with the
+/// exited PID), or None if `pid` was not a service.
+pub fn mark_service_exited(pid: u32, exit: Exit, oom_killed: bool) -> Option<ServiceInfo> {
+    ask(|r| Command::Exited(pid, exit, oom_killed, r))
 }
 
 /// Increment the restart count for a service.
 pub fn increment_restart_count(name: &str) {
-    let mut status = SERVICES.lock().expect("services lock poisoned");
-    if let Some(info) = status.services.get_mut(name) {
-        info.restart_count += 1;
-    }
+    send(Command::IncrementRestartCount(name.to_string()));
+}
+
+/// Record the health-check verdict on the run of the service whose process
+/// is `pid`. Ignored once that process has exited.
+pub fn set_health(pid: u32, health: Health) {
+    send(Command::SetHealth(pid, health));
 }
 
 pub fn deregister_service(name: &str) {
-    let mut status = SERVICES.lock().expect("services lock poisoned");
-    status.services.remove(name);
+    send(Command::Deregister(name.to_string()));
+}
+
+/// Swap in a re-read definition for a registered service, keeping its runtime
+/// state (PID, uptime, restart count). Returns the previous config, or None if
+/// the service is not registered.
+pub fn replace_config(
+    name: &str,
+    config: ServiceConfig,
+    config_path: Option<String>,
+) -> Option<ServiceConfig> {
+    ask(|r| Command::ReplaceConfig(name.to_string(), Box::new(config), config_path, r))
+}
+
+/// Receive every service table change from now on.
+pub fn subscribe() -> broadcast::Receiver<ServiceEvent> {
+    EVENTS.subscribe()
+}
+
+/// Log every state transition to the service's own log.
+pub fn log_transitions() {
+    let mut events = subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(ServiceEvent::StateChanged {
+                    service,
+                    old,
+                    new,
+                    reason,
+                    ..
+                }) => crate::logger::write_log(
+                    &service,
+                    &format!("State {} -> {} ({})", old.as_str(), new.as_str(), reason),
+                ),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_machine_allows_only_lifecycle_transitions() {
+        use ServiceState::*;
+        assert!(Inactive.can_become(Starting));
+        assert!(Starting.can_become(Running));
+        assert!(Running.can_become(Stopping));
+        assert!(Stopping.can_become(Inactive));
+        assert!(ConditionSkipped.can_become(Starting));
+        assert!(!Inactive.can_become(Running));
+        assert!(!Stopping.can_become(Running));
+        assert!(!Running.can_become(Starting));
+    }
+
+    #[test]
+    fn the_actor_tracks_a_run_and_broadcasts_each_transition() {
+        let mut events = subscribe();
+        let name = "actor-test".to_string();
+        register_service(
+            name.clone(),
+            ServiceInfo {
+                name: name.clone(),
+                ..Default::default()
+            },
+        );
+        assert!(begin_run(&name, None));
+        assert!(!begin_run(&name, None));
+        mark_service_started(&name, 4242);
+        let info = get_service(&name).unwrap();
+        assert!(info.is_running);
+        assert_eq!(info.pid, Some(4242));
+        assert_eq!(get_service_by_pid(4242).unwrap().name, name);
+
+        let crash = Exit::Signal {
+            signal: libc::SIGSEGV,
+            core_dumped: true,
+        };
+        let before = mark_service_exited(4242, crash, false).unwrap();
+        assert_eq!(before.pid, Some(4242));
+        let info = get_service(&name).unwrap();
+        assert_eq!(info.state, ServiceState::Failed);
+        assert!(!info.is_running);
+        assert!(mark_service_exited(4242, crash, false).is_none());
+        assert!(get_service_by_pid(4242).is_none());
+
+        // The restart is a second run, with its reason and a failed hook.
+        assert!(begin_run(&name, Some("on-failure policy")));
+        record_hook_failure(&name, "exec-start-pre: exited 1".to_string());
+        assert!(set_state(
+            &name,
+            ServiceState::Failed,
+            "exec-start-pre failed"
+        ));
+        let runs = run_history(&name);
+        assert_eq!(runs.len(), 2);
+        assert_eq!((runs[0].pid, runs[0].exit), (Some(4242), Some(crash)));
+        assert!(runs[0].ended.is_some());
+        assert_eq!(runs[1].restart_reason.as_deref(), Some("on-failure policy"));
+        assert_eq!(runs[1].hook_failures, ["exec-start-pre: exited 1"]);
+        assert!(runs[1].ended.is_some() && runs[1].pid.is_none());
+        // Not a transition the machine allows.
+        assert!(!set_state(&name, ServiceState::Stopping, "test"));
+        deregister_service(&name);
+        assert!(get_service(&name).is_none());
+
+        let mut ours = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event {
+                ServiceEvent::Added { ref service }
+                | ServiceEvent::Removed { ref service }
+                | ServiceEvent::StateChanged { ref service, .. }
+                    if *service == name =>
+                {
+                    ours.push(event)
+                }
+                _ => {}
+            }
+        }
+        let states: Vec<_> = ours
+            .iter()
+            .filter_map(|e| match e {
+                ServiceEvent::StateChanged { new, .. } => Some(*new),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ServiceState::Starting,
+                ServiceState::Running,
+                ServiceState::Failed,
+                ServiceState::Starting,
+                ServiceState::Failed
+            ]
+        );
+        assert_eq!(
+            ours.first(),
+            Some(&ServiceEvent::Added {
+                service: name.clone()
+            })
+        );
+        assert_eq!(ours.last(), Some(&ServiceEvent::Removed { service: name }));
+    }
+
+    #[test]
+    fn run_history_keeps_the_newest_runs_last() {
+        let name = "history-test".to_string();
+        register_service(
+            name.clone(),
+            ServiceInfo {
+                name: name.clone(),
+                ..Default::default()
+            },
+        );
+        let total = RUN_HISTORY_LEN + 4;
+        for i in 0..total {
+            let reason = format!("attempt {}", i);
+            assert!(begin_run(&name, (i > 0).then_some(reason.as_str())));
+            record_hook_failure(&name, format!("exec-start-pre: exited {}", i));
+            assert!(set_state(
+                &name,
+                ServiceState::Failed,
+                "exec-start-pre failed"
+            ));
+        }
+        let runs = run_history(&name);
+        assert_eq!(runs.len(), RUN_HISTORY_LEN);
+        // The oldest runs were dropped; the last is the newest.
+        let first = total - RUN_HISTORY_LEN;
+        for (run, i) in runs.iter().zip(first..) {
+            assert_eq!(run.restart_reason, Some(format!("attempt {}", i)));
+            assert_eq!(run.hook_failures, [format!("exec-start-pre: exited {}", i)]);
+            assert!(run.ended.is_some() && run.pid.is_none() && run.exit.is_none());
+        }
+        assert!(runs.windows(2).all(|w| w[0].started <= w[1].started));
+        deregister_service(&name);
+    }
+
+    #[test]
+    fn exits_describe_the_code_or_signal() {
+        assert_eq!(Exit::Code(0).describe(), "exited 0");
+        assert_eq!(Exit::Code(3).describe(), "exited 3");
+        let kill = |signal, core_dumped| Exit::Signal {
+            signal,
+            core_dumped,
+        };
+        assert_eq!(kill(libc::SIGTERM, false).describe(), "killed by SIGTERM");
+        assert_eq!(
+            kill(libc::SIGSEGV, true).describe(),
+            "killed by SIGSEGV (core dumped)"
+        );
+        assert_eq!(kill(200, false).describe(), "killed by signal 200");
+        assert_eq!(kill(libc::SIGKILL, false).code(), None);
+    }
 }
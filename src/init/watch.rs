@@ -0,0 +1,355 @@
+//! Automatic reload: watch the service directories with inotify.
+//!
+//! Every directory in `parser::service_dirs()` is watched recursively (app ids
+//! nest, e.g. `com.vendor.app/backup/cloud.rsc`), and so is each active user's
+//! vault service directory while their Lane is up. Changes to `.rsc` files are
+//! collected by a reader thread and handed to an applier thread, which waits for
+//! the directory to go quiet for [`DEBOUNCE`] before acting, so an editor's
+//! write-rename dance or `rev install` copying a tree is applied once.
+//!
+//! A change under a system directory runs the same daemon-reload as the `rescan`
+//! bus message (see `init::reload`). A change under a user's vault is applied
+//! to their Lane's service table (see `service::user_services`): a new service
+//! is started, an edited one takes its new definition and is restarted if it
+//! was running, and a removed one is stopped and dropped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+
+/// How long the watched trees must be quiet before a batch of changes is applied.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// The events that can change the set of service files under a directory.
+/// `IN_CLOSE_WRITE` rather than `IN_MODIFY`, so a half-written file is never
+/// parsed.
+fn watch_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_ONLYDIR
+}
+
+/// Which service set a watched directory feeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Root {
+    /// One of the system service directories.
+    System,
+    /// A user's vault service directory, loaded on their Lane.
+    Lane { uid: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Added => "added",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+        }
+    }
+}
+
+/// One detected change to a `.rsc` file.
+#[derive(Debug, Clone)]
+struct Change {
+    path: PathBuf,
+    kind: ChangeKind,
+    root: Root,
+}
+
+struct Watcher {
+    inotify: Inotify,
+    /// Watch descriptor -> the directory it watches and the set it belongs to.
+    dirs: Mutex<HashMap<WatchDescriptor, (PathBuf, Root)>>,
+}
+
+static WATCHER: OnceLock<Watcher> = OnceLock::new();
+
+impl Watcher {
+    /// Watch `dir` and every directory below it.
+    fn add_tree(&self, dir: &Path, root: Root) {
+        let mut dirs = self.dirs.lock().expect("watch lock poisoned");
+        for entry in walkdir::WalkDir::new(dir).into_iter().flatten() {
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+            match self.inotify.add_watch(entry.path(), watch_flags()) {
+                Ok(wd) => {
+                    dirs.insert(wd, (entry.path().to_path_buf(), root));
+                }
+                Err(e) => eprintln!("rev: watch: cannot watch {}: {}", entry.path().display(), e),
+            }
+        }
+    }
+
+    /// Turn one inotify event into the `.rsc` changes it implies. A directory
+    /// created or moved into a watched tree is watched in turn, and any service
+    /// files already inside it count as added.
+    fn changes_for(&self, event: InotifyEvent) -> Vec<Change> {
+        if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+            // The directory is gone (or was unwatched); forget its descriptor.
+            self.dirs
+                .lock()
+                .expect("watch lock poisoned")
+                .remove(&event.wd);
+            return Vec::new();
+        }
+        let Some((dir, root)) = self
+            .dirs
+            .lock()
+            .expect("watch lock poisoned")
+            .get(&event.wd)
+            .cloned()
+        else {
+            return Vec::new();
+        };
+        let Some(name) = event.name else {
+            return Vec::new();
+        };
+        let path = dir.join(name);
+
+        if event.mask.contains(AddWatchFlags::IN_ISDIR) {
+            if event
+                .mask
+                .intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO)
+            {
+                self.add_tree(&path, root);
+                return walkdir::WalkDir::new(&path)
+                    .into_iter()
+                    .flatten()
+                    .filter(|e| is_service_file(e.path()))
+                    .map(|e| Change {
+                        path: e.path().to_path_buf(),
+                        kind: ChangeKind::Added,
+                        root,
+                    })
+                    .collect();
+            }
+            // A removed subtree: the system reload notices the missing files by
+            // name, so one change for the directory is enough to trigger it.
+            return vec![Change {
+                path,
+                kind: ChangeKind::Removed,
+                root,
+            }];
+        }
+
+        if !is_service_file(&path) {
+            return Vec::new();
+        }
+        let kind = if event
+            .mask
+            .intersects(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO)
+        {
+            ChangeKind::Added
+        } else if event.mask.contains(AddWatchFlags::IN_CLOSE_WRITE) {
+            ChangeKind::Modified
+        } else {
+            ChangeKind::Removed
+        };
+        vec![Change { path, kind, root }]
+    }
+}
+
+fn is_service_file(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("rsc")
+}
+
+/// Collapse a burst of changes to one per path. A file created and then written
+/// is still "added"; otherwise the latest event for a path wins.
+fn coalesce(batch: Vec<Change>) -> Vec<Change> {
+    let mut by_path: BTreeMap<PathBuf, Change> = BTreeMap::new();
+    for change in batch {
+        match by_path.get_mut(&change.path) {
+            Some(prev) if prev.kind == ChangeKind::Added && change.kind == ChangeKind::Modified => {
+                // Created, then written: still a new file.
+            }
+            Some(prev) => *prev = change,
+            None => {
+                by_path.insert(change.path.clone(), change);
+            }
+        }
+    }
+    by_path.into_values().collect()
+}
+
+/// Log and act on one debounced batch.
+fn apply(batch: Vec<Change>) {
+    let changes = coalesce(batch);
+    let mut system_changed = false;
+    for change in &changes {
+        let line = format!("watch: {} {}", change.path.display(), change.kind.as_str());
+        println!("rev: {}", line);
+        crate::logger::write_log("rev", &line);
+
+        match change.root {
+            Root::System => system_changed = true,
+            Root::Lane { uid } => match change.kind {
+                ChangeKind::Added => crate::service::user_services::add(uid, &change.path),
+                ChangeKind::Modified => crate::service::user_services::update(uid, &change.path),
+                ChangeKind::Removed => crate::service::user_services::remove(uid, &change.path),
+            },
+        }
+    }
+    if system_changed {
+        let report = super::reload::reload();
+        println!("rev: {}", report.summary());
+    }
+}
+
+/// Start watching the system service directories. Spawns the reader and
+/// applier threads; a failure to set up inotify is logged and leaves manual
+/// `rescan` as the only way to reload.
+pub fn start() {
+    let inotify = match Inotify::init(InitFlags::IN_CLOEXEC) {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!(
+                "rev: watch: inotify unavailable ({}), automatic reload disabled",
+                e
+            );
+            return;
+        }
+    };
+    let watcher = WATCHER.get_or_init(|| Watcher {
+        inotify,
+        dirs: Mutex::new(HashMap::new()),
+    });
+    for dir in crate::parser::service_dirs() {
+        if dir.exists() {
+            watcher.add_tree(&dir, Root::System);
+        }
+    }
+
+    let (tx, rx) = mpsc::channel::<Change>();
+
+    thread::spawn(move || {
+        loop {
+            match watcher.inotify.read_events() {
+                Ok(events) => {
+                    for event in events {
+                        for change in watcher.changes_for(event) {
+                            let _ = tx.send(change);
+                        }
+                    }
+                }
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(e) => {
+                    eprintln!("rev: watch: inotify read failed: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    thread::spawn(move || {
+        while let Ok(first) = rx.recv() {
+            let mut batch = vec![first];
+            while let Ok(change) = rx.recv_timeout(DEBOUNCE) {
+                batch.push(change);
+            }
+            apply(batch);
+        }
+    });
+}
+
+/// Watch a user's vault service directory while their Lane is up. The directory
+/// is root-provisioned, so rev creates it if the user has never installed a
+/// service. No-op when the watcher is not running.
+pub fn watch_lane(uid: u32, dir: &Path) {
+    let Some(watcher) = WATCHER.get() else { return };
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        eprintln!("rev: watch: cannot create {}: {}", dir.display(), e);
+        return;
+    }
+    watcher.add_tree(dir, Root::Lane { uid });
+}
+
+/// Stop watching a user's service directory (their Lane is going down).
+pub fn unwatch_lane(uid: u32) {
+    let Some(watcher) = WATCHER.get() else { return };
+    let mut dirs = watcher.dirs.lock().expect("watch lock poisoned");
+    let lane_wds: Vec<WatchDescriptor> = dirs
+        .iter()
+        .filter(|(_, (_, root))| matches!(root, Root::Lane { uid: u } if *u == uid))
+        .map(|(wd, _)| *wd)
+        .collect();
+    for wd in lane_wds {
+        let _ = watcher.inotify.rm_watch(wd);
+        dirs.remove(&wd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, kind: ChangeKind) -> Change {
+        Change {
+            path: PathBuf::from(path),
+            kind,
+            root: Root::System,
+        }
+    }
+
+    #[test]
+    fn coalesce_keeps_one_change_per_path() {
+        let out = coalesce(vec![
+            change("/s/a.rsc", ChangeKind::Added),
+            change("/s/a.rsc", ChangeKind::Modified),
+            change("/s/b.rsc", ChangeKind::Modified),
+            change("/s/b.rsc", ChangeKind::Removed),
+        ]);
+        let kinds: Vec<(String, ChangeKind)> = out
+            .iter()
+            .map(|c| (c.path.display().to_string(), c.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("/s/a.rsc".to_string(), ChangeKind::Added),
+                ("/s/b.rsc".to_string(), ChangeKind::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_directories_are_watched() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("com.example.app").join("backup");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        let watcher = Watcher {
+            inotify: Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK).unwrap(),
+            dirs: Mutex::new(HashMap::new()),
+        };
+        watcher.add_tree(dir.path(), Root::System);
+
+        std::fs::write(nested.join("cloud.rsc"), "name = \"x\"").unwrap();
+        let changes: Vec<Change> = watcher
+            .inotify
+            .read_events()
+            .unwrap()
+            .into_iter()
+            .flat_map(|e| watcher.changes_for(e))
+            .collect();
+        let changes = coalesce(changes);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, nested.join("cloud.rsc"));
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+    }
+}
@@ -11,12 +11,12 @@ use std::io::{self, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use chrono::Utc;
 
-const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024; // 10 MB per log file
-const MAX_LOG_FILES: u32 = 5; // keep up to 5 rotated logs
-
 /// Returns the log directory path.
 fn log_dir() -> PathBuf {
-    if cfg!(debug_assertions) {
+    if cfg!(test) {
+        // Keep test runs out of the checkout's ./logs.
+        std::env::temp_dir().join("rev-test-logs")
+    } else if cfg!(debug_assertions) {
         PathBuf::from("./logs")
     } else {
         PathBuf::from("/Vault/Chronicle/rev")
@@ -35,10 +35,11 @@ pub fn open_log(service_name: &str) -> io::Result<File> {
     let path = log_path(service_name);
     fs::create_dir_all(path.parent().unwrap())?;
 
-    // Rotate if too large
+    // Rotate if too large (`max-log-size-mb` in rev.toml)
+    let settings = crate::settings::get();
     if let Ok(meta) = fs::metadata(&path) {
-        if meta.len() >= MAX_LOG_SIZE {
-            rotate(&path)?;
+        if meta.len() >= settings.max_log_size_mb << 20 {
+            rotate(&path, settings.max_log_files)?;
         }
     }
 
@@ -77,14 +78,14 @@ pub fn tail_log(service_name: &str, lines: usize) -> Vec<String> {
     all_lines[start..].to_vec()
 }
 
-/// Rotate log files: .log → .log.1, .log.1 → .log.2, etc.
-fn rotate(path: &Path) -> io::Result<()> {
+/// Rotate log files: .log → .log.1, .log.1 → .log.2, etc., keeping `keep`.
+fn rotate(path: &Path, keep: u32) -> io::Result<()> {
     // Remove oldest
-    let oldest = format!("{}.{}", path.display(), MAX_LOG_FILES);
+    let oldest = format!("{}.{}", path.display(), keep);
     let _ = fs::remove_file(&oldest);
 
     // Shift existing: .log.4 → .log.5, .log.3 → .log.4, etc.
-    for i in (1..MAX_LOG_FILES).rev() {
+    for i in (1..keep).rev() {
         let from = format!("{}.{}", path.display(), i);
         let to = format!("{}.{}", path.display(), i + 1);
         if Path::new(&from).exists() {
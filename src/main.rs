@@ -8,6 +8,7 @@ mod parser;
 mod seat;
 mod service;
 mod session;
+mod settings;
 
 #[tokio::main]
 async fn main() {
@@ -19,7 +20,10 @@ async fn main() {
         .split(std::path::MAIN_SEPARATOR)
         .last()
         .unwrap_or("");
-    if invocation_name.eq("init") || std::process::id() == 1 {
+    // The kernel runs the core dump helper through rev's own path, which is
+    // `init` when rev was booted as such.
+    let coredump_handler = args.get(1).is_some_and(|a| a == "coredump-handler");
+    if !coredump_handler && (invocation_name.eq("init") || std::process::id() == 1) {
         init::run(true).await;
     } else {
         cli::run(&args).await;
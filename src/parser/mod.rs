@@ -33,14 +33,32 @@ pub fn service_dirs() -> Vec<PathBuf> {
 // ---------------------------------------------------------------------------
 
 /// A wrapper for a cron string that validates on deserialization.
+///
+/// Accepts the usual five fields, or six with a leading seconds field
+/// (`*/15 * * * * *` fires every 15 seconds).
 #[derive(Clone, PartialEq, Eq)]
 pub struct CronStr(pub String);
 
+impl CronStr {
+    /// The parsed expression. A `CronStr` is validated when it is built, so
+    /// this cannot fail.
+    pub fn cron(&self) -> Cron {
+        parse_cron(&self.0).expect("CronStr holds a validated expression")
+    }
+}
+
+fn parse_cron(s: &str) -> Result<Cron, croner::errors::CronError> {
+    croner::parser::CronParser::builder()
+        .seconds(croner::parser::Seconds::Optional)
+        .build()
+        .parse(s)
+}
+
 impl FromStr for CronStr {
     type Err = croner::errors::CronError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Cron::from_str(s)?; // validate
+        parse_cron(s)?; // validate
         Ok(CronStr(s.to_string()))
     }
 }
@@ -72,16 +90,151 @@ impl<'de> Deserialize<'de> for CronStr {
         D: serde::Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Cron::from_str(&s).map_err(serde::de::Error::custom)?;
+        parse_cron(&s).map_err(serde::de::Error::custom)?;
         Ok(CronStr(s))
     }
 }
 
+// ---------------------------------------------------------------------------
+// ManagedDir
+// ---------------------------------------------------------------------------
+
+/// Mode of a managed directory that sets none.
+pub const DEFAULT_DIR_MODE: u32 = 0o755;
+
+/// A directory rev creates and owns for the service (`state-directory` and
+/// the like): `"name"`, or `{ name = "name", mode = 0o750 }`. The name is
+/// relative to the directory kind's base and is validated on deserialization,
+/// so it can never point outside it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "ManagedDirSpec", into = "ManagedDirSpec")]
+pub struct ManagedDir {
+    pub name: String,
+    pub mode: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ManagedDirSpec {
+    Name(String),
+    Table { name: String, mode: Option<u32> },
+}
+
+impl TryFrom<ManagedDirSpec> for ManagedDir {
+    type Error = String;
+
+    fn try_from(spec: ManagedDirSpec) -> Result<Self, Self::Error> {
+        let (name, mode) = match spec {
+            ManagedDirSpec::Name(name) => (name, None),
+            ManagedDirSpec::Table { name, mode } => (name, mode),
+        };
+        let escapes = std::path::Path::new(&name)
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)));
+        if name.is_empty() || escapes {
+            return Err(format!(
+                "directory name '{}' must be a relative path without '..'",
+                name
+            ));
+        }
+        let mode = mode.unwrap_or(DEFAULT_DIR_MODE);
+        if mode > 0o7777 {
+            return Err(format!("directory mode {:o} is not a file mode", mode));
+        }
+        Ok(ManagedDir { name, mode })
+    }
+}
+
+impl From<ManagedDir> for ManagedDirSpec {
+    fn from(dir: ManagedDir) -> Self {
+        if dir.mode == DEFAULT_DIR_MODE {
+            ManagedDirSpec::Name(dir.name)
+        } else {
+            ManagedDirSpec::Table {
+                name: dir.name,
+                mode: Some(dir.mode),
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ScheduleOverlap
+// ---------------------------------------------------------------------------
+
+/// What a timer firing does when the previous run is still going.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScheduleOverlap {
+    /// Leave the running instance alone; this run is skipped.
+    Skip,
+    /// Run once more as soon as the current run exits. Further firings while
+    /// one is queued collapse into it.
+    Queue,
+    /// Stop the running instance and start a fresh one.
+    Replace,
+}
+
+// ---------------------------------------------------------------------------
+// HealthCheck
+// ---------------------------------------------------------------------------
+
+/// A probe rev runs periodically while the service runs (`health-check`). It
+/// passes when `exec` exits 0 within `timeout` seconds; after `retries`
+/// consecutive failures the service is restarted.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct HealthCheck {
+    pub exec: String,
+    /// Seconds between probes (default 30).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    /// Seconds a probe may run before it is killed and counted failed
+    /// (default 5).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Consecutive failures that make the service unhealthy (default 3).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+impl HealthCheck {
+    pub fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.interval.unwrap_or(30).max(1))
+    }
+
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout.unwrap_or(5).max(1))
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries.unwrap_or(3).max(1)
+    }
+}
+
+/// What the last health-check probes of a running service said.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Health {
+    Healthy,
+    /// `retries` probes in a row failed.
+    Unhealthy,
+}
+
+impl Health {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Health::Healthy => "healthy",
+            Health::Unhealthy => "unhealthy",
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // RestartPolicy
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum RestartPolicy {
     Always,
@@ -91,6 +244,20 @@ pub enum RestartPolicy {
     OnResourceChange,
 }
 
+/// What rev does when the kernel OOM-kills a process of the service.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OomPolicy {
+    /// Stop the service, and never restart it after an OOM kill.
+    Stop,
+    /// Restart the service, whatever its restart policy.
+    Restart,
+    /// Leave the service running; an OOM-killed main process is handled by the
+    /// restart policy like any other crash.
+    #[default]
+    Continue,
+}
+
 /// Where and when a service runs. This is data on the service, not a function of
 /// which directory it lives in, so the same `/Core/Services` tree can hold both
 /// system services and per-user defaults.
@@ -110,7 +277,7 @@ pub enum ServiceScope {
 // EnvMap
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct EnvMap(pub HashMap<String, String>);
 
@@ -161,7 +328,7 @@ impl<'a> IntoIterator for &'a EnvMap {
 // ServiceConfig — persisted as TOML in .rsc files
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct ServiceConfig {
     pub name: String,
@@ -186,6 +353,15 @@ pub struct ServiceConfig {
     pub working_dir: Option<PathBuf>,
     #[serde(default)]
     pub restart_policy: RestartPolicy,
+    /// Restarts the policy may make within `start-limit-interval-sec`
+    /// (default 5). Past that it gives up: the service is left failed and its
+    /// `on-failure` services start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_limit_burst: Option<u32>,
+    /// The window, in seconds, `start-limit-burst` counts restarts in
+    /// (default 10).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_limit_interval_sec: Option<u64>,
     /// Whether this service runs system-wide at boot or per-user at login.
     #[serde(default)]
     pub scope: ServiceScope,
@@ -194,6 +370,11 @@ pub struct ServiceConfig {
     /// and waits for it to register the name before answering the Lookup.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub provides: Vec<String>,
+    /// Stop the service once none of its `provides` names has been looked up
+    /// for this many seconds (at least 1). A clean stop: the next Lookup
+    /// activates it again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_exit_sec: Option<u64>,
     /// Ordering: start this service after the named services (systemd `After=`).
     /// Pure ordering, no dependency is implied (use `requires`/`wants` for that).
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -210,6 +391,11 @@ pub struct ServiceConfig {
     /// present, but their absence or failure is tolerated.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub wants: Vec<String>,
+    /// WireBus names that must be registered on the Highway before this
+    /// service is forked. Their providers are bus-activated if they are idle;
+    /// the start fails if the names are not there within `timeout-start`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires_bus_name: Vec<String>,
     /// Run the service process as this user (systemd `User=`): a UAC account
     /// name or a numeric uid. Default is rev's own uid (root). The group
     /// defaults to the account's primary group.
@@ -219,12 +405,257 @@ pub struct ServiceConfig {
     /// gid. Overrides the user's primary group when set.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub group: Option<String>,
+    /// Run the service process as an ephemeral uid/gid that rev allocates when
+    /// it starts and releases when it exits (systemd `DynamicUser=`), so a
+    /// small daemon needs no UAC account. Overrides `user` and `group`.
+    #[serde(default)]
+    pub dynamic_user: bool,
+    /// Persistent state, created as `/Vault/State/<name>` and exported as
+    /// `STATE_DIRECTORY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_directory: Option<ManagedDir>,
+    /// Cache that may be thrown away, created as
+    /// `/Transit/Volatile/cache/<name>` and exported as `CACHE_DIRECTORY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_directory: Option<ManagedDir>,
+    /// Runtime files (sockets, PID files), created as
+    /// `/Transit/Ephemeral/run/<name>`, exported as `RUNTIME_DIRECTORY`, and
+    /// removed when the process exits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime_directory: Option<ManagedDir>,
+    /// The service's own log files, created as `/Vault/Chronicle/<name>` and
+    /// exported as `LOGS_DIRECTORY`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logs_directory: Option<ManagedDir>,
+    /// Seconds a start may wait for its `requires-bus-name` names (default 90).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_start: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub timeout_stop: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub schedule: Option<CronStr>,
+    /// Shorthand for `schedule-overlap = "replace"`, kept for existing files.
     #[serde(default)]
     pub force_restart_on_schedule: bool,
+    /// What a timer firing does while the previous run is still going. Default
+    /// `skip` (or `replace` with `force-restart-on-schedule`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_overlap: Option<ScheduleOverlap>,
+    /// IANA time zone the `schedule` is read in (`Europe/Berlin`). Default UTC.
+    /// `0 3 * * *` then means 03:00 Berlin time all year; across a DST change
+    /// a time that does not exist fires at the next one that does, and a time
+    /// that occurs twice fires once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_timezone: Option<chrono_tz::Tz>,
+    /// Catch up on missed `schedule` runs (systemd `Persistent=`). The time of
+    /// the last scheduled run is kept on disk; a run that fell while the
+    /// machine was off or asleep fires as soon as rev sees it was missed.
+    #[serde(default)]
+    pub persistent: bool,
+    /// Start the service this many seconds after the kernel booted (systemd
+    /// `OnBootSec=`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_boot_sec: Option<u64>,
+    /// Start the service this many seconds after rev started (systemd
+    /// `OnStartupSec=`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_startup_sec: Option<u64>,
+    /// Start the service this many seconds after its timers were loaded, at
+    /// boot or by a reload (systemd `OnActiveSec=`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_active_sec: Option<u64>,
+    /// Start the service this many seconds after it last stopped (systemd
+    /// `OnUnitInactiveSec=`). Repeats, so with `on-boot-sec` it makes a
+    /// periodic job that never overlaps itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_unit_inactive_sec: Option<u64>,
+    /// Delay every timer and schedule by a random 0..=N seconds (systemd
+    /// `RandomizedDelaySec=`), so a fleet does not fire in lockstep.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub randomized_delay_sec: Option<u64>,
+    /// Start the service when one of these paths is created, removed, renamed,
+    /// has its attributes changed, or is closed after a write (systemd
+    /// `PathChanged=`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_path_changed: Vec<PathBuf>,
+    /// Start the service when one of these paths exists, at load or when it
+    /// appears (systemd `PathExists=`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_path_exists: Vec<PathBuf>,
+    /// Start the service when one of these directories holds at least one entry
+    /// (systemd `DirectoryNotEmpty=`). A spool or drop directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_directory_not_empty: Vec<PathBuf>,
+    /// Like `on-path-changed`, but also fires on every write, not only when the
+    /// writer closes the file (systemd `PathModified=`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_path_modified: Vec<PathBuf>,
+    /// Stop the service when its .rsc file is deleted and rev reloads. Off by
+    /// default, so removing a file never kills a running service by surprise:
+    /// the entry is dropped from the table once the process exits instead.
+    #[serde(default)]
+    pub stop_on_remove: bool,
+    /// Services to start when this one fails and the restart policy gives up
+    /// on it: it exited non-zero or was killed without a stop request, and the
+    /// policy does not restart it, has hit its start limit, or failed to start
+    /// it again. An exit that is restarted never fires them, so a crash loop
+    /// starts them once, when it ends. `%n` in a target is replaced by this
+    /// service's name, and a target `name@instance` that is not loaded runs
+    /// the template `name@` as that instance. Every target must resolve when
+    /// the service is loaded. They get the failed service's name and exit
+    /// status in their environment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_failure: Vec<String>,
+    /// Services to start when this one exits 0 on its own and is not restarted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_success: Vec<String>,
+    /// Keep at most this many MiB of each core the service dumps (default
+    /// 256). 0 records the crash without storing the core.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coredump_max_mb: Option<u64>,
+    /// Keep this many of the service's most recent cores (default 5).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coredump_keep: Option<u32>,
+    #[serde(default)]
+    pub oom_policy: OomPolicy,
+    /// Command run when the service's cgroup comes under memory pressure
+    /// (PSI), before the kernel has to OOM-kill anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_pressure_action: Option<String>,
+    /// A command run periodically to check that the process still does its
+    /// job; the service is restarted after repeated failures.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
+    /// Cap the memory of the service's cgroup at this many MiB (`memory.max`).
+    /// Beyond it the kernel reclaims, then OOM-kills within the cgroup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_max_mb: Option<u64>,
+    /// Cap the service's CPU time at this percentage of one CPU (`cpu.max`);
+    /// 200 allows two full CPUs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<u32>,
+    /// Cap the number of processes and threads in the service's cgroup
+    /// (`pids.max`), so a fork bomb stays contained.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tasks_max: Option<u32>,
+    /// Secrets fetched from RookGuard at each start and handed to the service
+    /// as files in `CREDENTIALS_DIRECTORY`, never through the environment.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub credentials: Vec<String>,
+}
+
+impl ServiceConfig {
+    /// How long the service may go without a lookup, if it exits when idle.
+    /// 0 counts as 1, so it is never stopped before a client could find it.
+    pub fn idle_exit(&self) -> Option<std::time::Duration> {
+        self.idle_exit_sec.map(|s| std::time::Duration::from_secs(s.max(1)))
+    }
+
+    /// How many restarts the policy may make, and within how long.
+    pub fn start_limit(&self) -> (u32, std::time::Duration) {
+        (
+            self.start_limit_burst.unwrap_or(5),
+            std::time::Duration::from_secs(self.start_limit_interval_sec.unwrap_or(10)),
+        )
+    }
+
+    /// Seconds a stop waits after SIGTERM before SIGKILL: `timeout-stop`, else
+    /// rev.toml's default.
+    pub fn stop_timeout(&self) -> u64 {
+        self.timeout_stop
+            .unwrap_or_else(|| crate::settings::get().timeout_stop)
+    }
+
+    /// Whether the service is started by a filesystem condition rather than at
+    /// boot (any of the `on-path-*` / `on-directory-not-empty` fields is set).
+    pub fn is_path_triggered(&self) -> bool {
+        !(self.on_path_changed.is_empty()
+            && self.on_path_exists.is_empty()
+            && self.on_directory_not_empty.is_empty()
+            && self.on_path_modified.is_empty())
+    }
+
+    /// The effective overlap policy for timer firings.
+    pub fn overlap_policy(&self) -> ScheduleOverlap {
+        match self.schedule_overlap {
+            Some(policy) => policy,
+            None if self.force_restart_on_schedule => ScheduleOverlap::Replace,
+            None => ScheduleOverlap::Skip,
+        }
+    }
+
+    /// Whether the service is started by a timer rather than at boot: any
+    /// monotonic timer, or a `persistent` schedule (which runs at boot only
+    /// when a run was missed).
+    pub fn is_timer_triggered(&self) -> bool {
+        self.on_boot_sec.is_some()
+            || self.on_startup_sec.is_some()
+            || self.on_active_sec.is_some()
+            || self.on_unit_inactive_sec.is_some()
+            || (self.persistent && self.schedule.is_some())
+    }
+
+    /// Whether the service is a template (its name ends in `@`): never started
+    /// itself, only run as an instance `name@instance` by a trigger.
+    pub fn is_template(&self) -> bool {
+        self.name.ends_with('@')
+    }
+}
+
+// ---------------------------------------------------------------------------
+// ServiceState — lifecycle state machine
+// ---------------------------------------------------------------------------
+
+/// Where a service is in its lifecycle. Owned by the service table
+/// (`init::services`), which only applies transitions `can_become` allows.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServiceState {
+    /// Loaded, not running.
+    #[default]
+    Inactive,
+    /// Start hooks running or the process is being forked.
+    Starting,
+    Running,
+    /// A stop was requested; the process has not exited yet.
+    Stopping,
+    /// The last run failed to start, or exited non-zero or by a signal
+    /// without being asked to stop.
+    Failed,
+    /// Not started at boot: it waits for a path or timer condition.
+    ConditionSkipped,
+}
+
+impl ServiceState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ServiceState::Inactive => "inactive",
+            ServiceState::Starting => "starting",
+            ServiceState::Running => "running",
+            ServiceState::Stopping => "stopping",
+            ServiceState::Failed => "failed",
+            ServiceState::ConditionSkipped => "condition-skipped",
+        }
+    }
+
+    /// Whether a process exists in this state.
+    pub fn has_process(self) -> bool {
+        matches!(self, ServiceState::Running | ServiceState::Stopping)
+    }
+
+    /// Whether the state machine allows moving from `self` to `next`.
+    pub fn can_become(self, next: ServiceState) -> bool {
+        use ServiceState::*;
+        matches!(
+            (self, next),
+            (Inactive | Failed | ConditionSkipped, Starting)
+                | (Inactive | Failed, ConditionSkipped)
+                | (ConditionSkipped | Failed, Inactive)
+                | (Starting, Running | Failed | Inactive)
+                | (Running, Stopping | Inactive | Failed)
+                | (Stopping, Inactive | Failed)
+        )
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -235,6 +666,8 @@ pub struct ServiceConfig {
 pub struct ServiceInfo {
     pub name: String,
     #[serde(default)]
+    pub state: ServiceState,
+    #[serde(default)]
     pub is_running: bool,
     #[serde(default)]
     pub pid: Option<u32>,
@@ -252,47 +685,17 @@ pub struct ServiceInfo {
     pub tasks: Option<u32>,
     #[serde(default)]
     pub config_path: Option<String>,
+    /// Started by `RunTransient` rather than from a .rsc file: it has no
+    /// config path, and leaves the table when it exits for good.
+    #[serde(default)]
+    pub transient: bool,
+    /// The verdict of the health check on the current run; `None` without a
+    /// check or before its first probe.
+    #[serde(default)]
+    pub health: Option<Health>,
     pub config: ServiceConfig,
 }
 
-impl ServiceInfo {
-    /// Read /proc/<pid>/status and /proc/<pid>/stat to get live resource usage.
-    #[allow(dead_code)]
-    pub fn refresh_proc_stats(&mut self) {
-        let pid = match self.pid {
-            Some(p) if self.is_running => p,
-            _ => return,
-        };
-        // Memory: VmRSS from /proc/<pid>/status
-        if let Ok(status) = std::fs::read_to_string(format!("/proc/{}/status", pid)) {
-            for line in status.lines() {
-                if line.starts_with("VmRSS:") {
-                    if let Some(kb) = line.split_whitespace().nth(1).and_then(|s| s.parse::<u64>().ok()) {
-                        self.memory_bytes = Some(kb * 1024);
-                    }
-                }
-                if line.starts_with("Threads:") {
-                    if let Some(t) = line.split_whitespace().nth(1).and_then(|s| s.parse::<u32>().ok()) {
-                        self.tasks = Some(t);
-                    }
-                }
-            }
-        }
-        // CPU: utime + stime from /proc/<pid>/stat (fields 14,15 in clock ticks)
-        if let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
-            let fields: Vec<&str> = stat.split_whitespace().collect();
-            if fields.len() > 14 {
-                let utime = fields[13].parse::<u64>().unwrap_or(0);
-                let stime = fields[14].parse::<u64>().unwrap_or(0);
-                let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
-                if ticks_per_sec > 0 {
-                    self.cpu_seconds = Some((utime + stime) as f64 / ticks_per_sec as f64);
-                }
-            }
-        }
-    }
-}
-
 // ---------------------------------------------------------------------------
 // TOML serialization for .rsc files
 // ---------------------------------------------------------------------------
@@ -0,0 +1,169 @@
+//! Live resource usage of services.
+//!
+//! Figures come from the service's cgroup when it has one (memory current and
+//! peak, CPU, IO, tasks, OOM kills). Without a cgroup (debug builds, or a
+//! kernel without cgroup2) they are summed from `/proc` over the main process
+//! and everything it forked, so a service that works in child processes is
+//! not reported as idle.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::cgroup;
+use crate::bus::protocol::ServiceStats;
+use crate::parser::ServiceInfo;
+
+/// What one process contributes, from `/proc/<pid>`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct ProcUsage {
+    rss_bytes: u64,
+    cpu_ticks: u64,
+    threads: u32,
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+/// `(ppid, utime + stime, threads)` from `/proc/<pid>/stat`. The command name
+/// may contain spaces and parentheses, so fields are counted from its end.
+fn parse_stat(text: &str) -> Option<(u32, u64, u32)> {
+    let fields: Vec<&str> = text
+        .get(text.rfind(')')? + 1..)?
+        .split_whitespace()
+        .collect();
+    let ppid = fields.get(1)?.parse().ok()?;
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let threads = fields.get(17)?.parse().ok()?;
+    Some((ppid, utime + stime, threads))
+}
+
+fn proc_usage(pid: u32) -> Option<ProcUsage> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, cpu_ticks, threads) = parse_stat(&stat)?;
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap_or_default();
+    let rss_kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))
+        .and_then(|v| v.split_whitespace().next()?.parse::<u64>().ok())
+        .unwrap_or(0);
+    // Unreadable for processes of other users when rev is not root.
+    let io = std::fs::read_to_string(format!("/proc/{}/io", pid)).unwrap_or_default();
+    let io_field = |key: &str| {
+        io.lines()
+            .find_map(|l| l.strip_prefix(key))
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0)
+    };
+    Some(ProcUsage {
+        rss_bytes: rss_kb * 1024,
+        cpu_ticks,
+        threads,
+        read_bytes: io_field("read_bytes:"),
+        write_bytes: io_field("write_bytes:"),
+    })
+}
+
+/// `pid` and all of its descendants, from the parent links in `/proc`.
+fn process_tree(pid: u32) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for entry in std::fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let Some(child) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+            continue;
+        };
+        if let Some((ppid, _, _)) = std::fs::read_to_string(entry.path().join("stat"))
+            .ok()
+            .and_then(|s| parse_stat(&s))
+        {
+            children.entry(ppid).or_default().push(child);
+        }
+    }
+    let mut tree = vec![pid];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(kids) = children.get(&tree[i]) {
+            tree.extend(kids);
+        }
+        i += 1;
+    }
+    tree
+}
+
+fn ticks_per_sec() -> u64 {
+    (unsafe { libc::sysconf(libc::_SC_CLK_TCK) }).max(1) as u64
+}
+
+/// Current resource usage of a service. All figures are `None` when it is
+/// not running and has no cgroup.
+pub fn collect(info: &ServiceInfo) -> ServiceStats {
+    let cgroup = cgroup::existing(&info.name);
+    let processes = match (&cgroup, info.pid) {
+        (Some(cg), _) => cgroup::procs(cg),
+        (None, Some(pid)) if info.is_running => process_tree(pid),
+        (None, _) => Vec::new(),
+    };
+    let mut stats = ServiceStats {
+        service: info.name.clone(),
+        cgroup: cgroup.as_ref().map(|cg| cg.display().to_string()),
+        processes: processes.clone(),
+        ..Default::default()
+    };
+    match cgroup {
+        Some(cg) => fill_from_cgroup(&mut stats, &cg),
+        None if !processes.is_empty() => {
+            let usage = processes.iter().filter_map(|&p| proc_usage(p));
+            let total = usage.fold(ProcUsage::default(), |a, u| ProcUsage {
+                rss_bytes: a.rss_bytes + u.rss_bytes,
+                cpu_ticks: a.cpu_ticks + u.cpu_ticks,
+                threads: a.threads + u.threads,
+                read_bytes: a.read_bytes + u.read_bytes,
+                write_bytes: a.write_bytes + u.write_bytes,
+            });
+            stats.memory_bytes = Some(total.rss_bytes);
+            stats.cpu_usec = Some(total.cpu_ticks * 1_000_000 / ticks_per_sec());
+            stats.tasks = Some(total.threads);
+            stats.io_read_bytes = Some(total.read_bytes);
+            stats.io_write_bytes = Some(total.write_bytes);
+        }
+        None => {}
+    }
+    stats
+}
+
+fn fill_from_cgroup(stats: &mut ServiceStats, cg: &Path) {
+    stats.memory_bytes = cgroup::read_u64(cg, "memory.current");
+    stats.memory_peak_bytes = cgroup::read_u64(cg, "memory.peak");
+    stats.cpu_usec = cgroup::read_key(cg, "cpu.stat", "usage_usec");
+    stats.tasks = cgroup::read_u64(cg, "pids.current").map(|n| n as u32);
+    stats.oom_kills = cgroup::read_key(cg, "memory.events", "oom_kill");
+    if let Ok(io) = std::fs::read_to_string(cg.join("io.stat")) {
+        let (read, written) = cgroup::io_bytes(&io);
+        stats.io_read_bytes = Some(read);
+        stats.io_write_bytes = Some(written);
+    }
+}
+
+/// Fill the usage fields of `info` (memory, CPU, tasks) for a snapshot.
+pub fn refresh(info: &mut ServiceInfo) {
+    let stats = collect(info);
+    info.memory_bytes = stats.memory_bytes;
+    info.cpu_seconds = stats.cpu_usec.map(|us| us as f64 / 1_000_000.0);
+    info.tasks = stats.tasks;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_fields_are_counted_from_the_end_of_the_name() {
+        let stat = "4242 (my (odd) svc) S 1 4242 4242 0 -1 4194560 120 0 0 0 \
+                    250 50 0 0 20 0 3 0 12345 10485760 512 18446744073709551615";
+        assert_eq!(parse_stat(stat), Some((1, 300, 3)));
+
+        // Our own process tree holds at least ourselves.
+        let me = std::process::id();
+        assert_eq!(process_tree(me)[0], me);
+        let usage = proc_usage(me).unwrap();
+        assert!(usage.rss_bytes > 0 && usage.threads >= 1);
+    }
+}
@@ -0,0 +1,190 @@
+//! Per-service cgroups (cgroup v2).
+//!
+//! Each system service runs in its own cgroup, `/sys/fs/cgroup/rev/<name>`
+//! (`/` in the name becomes `_`), so everything it forks is accounted to it.
+//! rev creates the cgroup before forking and the child moves itself in before
+//! exec. The cgroup is kept after the service exits, so its counters (peak
+//! memory, OOM kills) can still be read; it is reused by the next start.
+//!
+//! Skipped in debug builds, like the early mounts: a dev run never touches the
+//! host's cgroup tree.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::parser::ServiceConfig;
+
+/// The cgroup all service cgroups live under.
+const ROOT: &str = "/sys/fs/cgroup/rev";
+
+/// Controllers enabled for service cgroups.
+const CONTROLLERS: &str = "+cpu +memory +io +pids";
+
+fn enabled() -> bool {
+    !cfg!(debug_assertions) && Path::new(ROOT).exists()
+}
+
+/// Create rev's cgroup and enable the controllers services need. Boot only,
+/// after cgroup2 is mounted.
+pub fn setup() {
+    if cfg!(debug_assertions) {
+        return;
+    }
+    if let Err(e) = fs::create_dir_all(ROOT) {
+        eprintln!("rev: cannot create {}: {}", ROOT, e);
+        return;
+    }
+    for parent in ["/sys/fs/cgroup", ROOT] {
+        let control = Path::new(parent).join("cgroup.subtree_control");
+        if let Err(e) = fs::write(&control, CONTROLLERS) {
+            eprintln!("rev: cannot enable controllers in {}: {}", parent, e);
+        }
+    }
+}
+
+/// The cgroup of service `name`, whether or not it exists.
+pub fn path(name: &str) -> PathBuf {
+    Path::new(ROOT).join(name.replace('/', "_"))
+}
+
+/// Create (or reuse) the cgroup for service `name`, for [`enter`] in the
+/// child. `None` when cgroups are not in use or it cannot be created; the
+/// service then runs in rev's own cgroup.
+pub fn prepare(name: &str) -> Option<PathBuf> {
+    if !enabled() {
+        return None;
+    }
+    let cgroup = path(name);
+    match fs::create_dir(&cgroup) {
+        Ok(()) => Some(cgroup),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Some(cgroup),
+        Err(e) => {
+            eprintln!("rev: cannot create cgroup {}: {}", cgroup.display(), e);
+            None
+        }
+    }
+}
+
+/// The `cpu.max` period, in microseconds.
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// The `(file, value)` writes that apply `config`'s resource limits. An unset
+/// limit is written as `max`, clearing what a previous run of a reused cgroup
+/// had.
+fn limits(config: &ServiceConfig) -> [(&'static str, String); 3] {
+    let or_max = |v: Option<u64>| v.map_or_else(|| "max".to_string(), |v| v.to_string());
+    let quota = config
+        .cpu_quota
+        .map(|percent| u64::from(percent) * CPU_PERIOD_USEC / 100);
+    [
+        (
+            "memory.max",
+            or_max(config.memory_max_mb.map(|mb| mb << 20)),
+        ),
+        ("cpu.max", format!("{} {}", or_max(quota), CPU_PERIOD_USEC)),
+        ("pids.max", or_max(config.tasks_max.map(u64::from))),
+    ]
+}
+
+/// Apply `config`'s resource limits to `cgroup`, before the service enters it.
+pub fn apply_limits(cgroup: &Path, config: &ServiceConfig) -> io::Result<()> {
+    for (file, value) in limits(config) {
+        fs::write(cgroup.join(file), value)?;
+    }
+    Ok(())
+}
+
+/// Move the calling process into `cgroup`. Called in the forked child.
+pub fn enter(cgroup: &Path) -> io::Result<()> {
+    fs::write(cgroup.join("cgroup.procs"), "0")
+}
+
+/// The cgroup of service `name` if it exists.
+pub fn existing(name: &str) -> Option<PathBuf> {
+    let cgroup = path(name);
+    (enabled() && cgroup.is_dir()).then_some(cgroup)
+}
+
+/// Remove the (empty) cgroup of a service that is gone for good.
+pub fn remove(name: &str) {
+    if let Some(cgroup) = existing(name) {
+        let _ = fs::remove_dir(cgroup);
+    }
+}
+
+/// How many times the kernel OOM-killed a process in service `name`'s cgroup.
+pub fn oom_kills(name: &str) -> Option<u64> {
+    read_key(&existing(name)?, "memory.events", "oom_kill")
+}
+
+/// The PIDs in `cgroup`.
+pub fn procs(cgroup: &Path) -> Vec<u32> {
+    fs::read_to_string(cgroup.join("cgroup.procs"))
+        .map(|text| text.lines().filter_map(|l| l.trim().parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// A single-number file, e.g. `memory.current`.
+pub fn read_u64(cgroup: &Path, file: &str) -> Option<u64> {
+    fs::read_to_string(cgroup.join(file))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// The value of `key` in a flat keyed file (`cpu.stat`, `memory.events`).
+pub fn read_key(cgroup: &Path, file: &str, key: &str) -> Option<u64> {
+    keyed(&fs::read_to_string(cgroup.join(file)).ok()?, key)
+}
+
+/// The value of `key` in `key value` lines.
+pub fn keyed(text: &str, key: &str) -> Option<u64> {
+    text.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        (k == key).then(|| v.trim().parse().ok())?
+    })
+}
+
+/// Bytes read and written across every device in `io.stat`.
+pub fn io_bytes(text: &str) -> (u64, u64) {
+    let mut read = 0;
+    let mut written = 0;
+    for field in text.split_whitespace() {
+        if let Some(n) = field.strip_prefix("rbytes=") {
+            read += n.parse().unwrap_or(0);
+        } else if let Some(n) = field.strip_prefix("wbytes=") {
+            written += n.parse().unwrap_or(0);
+        }
+    }
+    (read, written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cgroup_files_are_parsed_and_written() {
+        let cpu = "usage_usec 123456\nuser_usec 100000\nsystem_usec 23456\n";
+        assert_eq!(keyed(cpu, "usage_usec"), Some(123456));
+        assert_eq!(keyed(cpu, "usage"), None);
+        let io = "8:0 rbytes=4096 wbytes=512 rios=1 wios=1 dbytes=0 dios=0\n\
+                  259:0 rbytes=1024 wbytes=0 rios=1 wios=0 dbytes=0 dios=0\n";
+        assert_eq!(io_bytes(io), (5120, 512));
+        let config = ServiceConfig {
+            memory_max_mb: Some(64),
+            cpu_quota: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(
+            limits(&config).map(|(_, v)| v),
+            ["67108864", "50000 100000", "max"].map(String::from)
+        );
+        assert_eq!(
+            path("com.test.app/worker"),
+            Path::new(ROOT).join("com.test.app_worker")
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Core dump capture for crashed processes.
+//!
+//! At boot rev points the kernel's `core_pattern` at itself:
+//! `|<rev> coredump-handler %P %s %t %e`. For every crash the kernel runs that
+//! helper as root with the core image on stdin. The helper asks rev over the
+//! Highway which service owns the crashing PID, and with what limits
+//! (`coredump-max-mb`, `coredump-keep`), then stores the core under
+//! `/Vault/Chronicle/rev/cores/<service>/` next to a small TOML record. Cores
+//! of processes that are not a service's main process go under `other/`.
+//!
+//! `core_pipe_limit` is set as well, so the kernel keeps the crashed process
+//! around until the helper is done; its PID is still in the service table when
+//! rev is asked about it.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::bus::protocol::{self, Message, MessageBody};
+use crate::parser::ServiceConfig;
+
+/// Largest core kept when the service sets no `coredump-max-mb`.
+pub const DEFAULT_MAX_MB: u64 = 256;
+/// Cores kept per service when it sets no `coredump-keep`.
+pub const DEFAULT_KEEP: u32 = 5;
+/// Directory for cores of processes no service owns.
+const UNOWNED: &str = "other";
+/// How many core helpers the kernel may run at once.
+const PIPE_LIMIT: &str = "16";
+
+/// Where cores are stored, one directory per service.
+pub fn cores_dir() -> PathBuf {
+    if cfg!(debug_assertions) {
+        PathBuf::from("./Vault/Chronicle/rev/cores")
+    } else {
+        PathBuf::from("/Vault/Chronicle/rev/cores")
+    }
+}
+
+/// Who a core belongs to and how much of it to keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    pub service: Option<String>,
+    /// Bytes of the core kept; 0 records the crash without storing the core.
+    pub size_max: u64,
+    pub keep: u32,
+}
+
+impl Policy {
+    /// The limits a service's config asks for, or the defaults.
+    pub fn for_service(config: &ServiceConfig) -> Self {
+        Policy {
+            service: Some(config.name.clone()),
+            size_max: config.coredump_max_mb.unwrap_or(DEFAULT_MAX_MB) * 1024 * 1024,
+            keep: config.coredump_keep.unwrap_or(DEFAULT_KEEP),
+        }
+    }
+
+    /// The default limits, for a process no service owns.
+    pub fn unowned() -> Self {
+        Policy {
+            service: None,
+            size_max: DEFAULT_MAX_MB * 1024 * 1024,
+            keep: DEFAULT_KEEP,
+        }
+    }
+}
+
+/// The record stored next to each core.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CoreRecord {
+    /// `<timestamp>-<pid>`, unique within a service's directory.
+    #[serde(skip)]
+    pub id: String,
+    /// The service's directory the record is stored in.
+    #[serde(skip)]
+    pub dir: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    pub pid: u32,
+    pub signal: i32,
+    /// The crashed executable's name as the kernel reports it.
+    pub comm: String,
+    /// Unix seconds of the crash.
+    pub timestamp: i64,
+    /// Bytes of core stored.
+    pub size: u64,
+    /// Whether the core was cut off at the service's size limit.
+    pub truncated: bool,
+}
+
+impl CoreRecord {
+    /// The stored core, if any was kept.
+    pub fn core_path(&self) -> Option<PathBuf> {
+        let path = self.dir.join(format!("{}.core", self.id));
+        path.exists().then_some(path)
+    }
+}
+
+/// A service's directory under the cores directory `root`.
+fn service_dir(root: &Path, service: Option<&str>) -> PathBuf {
+    let name = service.map_or(UNOWNED.to_string(), super::scheduler::file_name_for);
+    root.join(name)
+}
+
+/// Make rev the kernel's core dump handler. Production boot only.
+pub fn install() {
+    let exe = match std::env::current_exe() {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("rev: cannot find own executable for core_pattern: {}", e);
+            return;
+        }
+    };
+    let pattern = format!("|{} coredump-handler %P %s %t %e", exe.display());
+    if let Err(e) = fs::write("/proc/sys/kernel/core_pattern", &pattern) {
+        eprintln!("rev: cannot set core_pattern: {}", e);
+        return;
+    }
+    if let Err(e) = fs::write("/proc/sys/kernel/core_pipe_limit", PIPE_LIMIT) {
+        eprintln!("rev: cannot set core_pipe_limit: {}", e);
+    }
+    println!(
+        "rev: core dumps are captured into {}",
+        cores_dir().display()
+    );
+}
+
+/// Ask rev which service owns `pid` and with what core limits. Falls back to
+/// the unowned defaults if rev cannot be reached, so a core is never dropped
+/// for that.
+fn lookup_policy(pid: u32) -> Policy {
+    let ask = || -> io::Result<Option<Policy>> {
+        let mut stream = std::os::unix::net::UnixStream::connect(crate::bus::socket_path())?;
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(2)))?;
+        let request = Message {
+            id: 1,
+            sender: format!("rev-coredump-{}", std::process::id()),
+            auth_token: None,
+            body: MessageBody::CoredumpOwner { pid },
+        };
+        protocol::sync::send_message(&mut stream, &request)?;
+        Ok(match protocol::sync::recv_message(&mut stream)?.body {
+            MessageBody::CoredumpPolicy {
+                service,
+                size_max,
+                keep,
+            } => Some(Policy {
+                service,
+                size_max,
+                keep,
+            }),
+            _ => None,
+        })
+    };
+    match ask() {
+        Ok(Some(policy)) => policy,
+        Ok(None) => Policy::unowned(),
+        Err(e) => {
+            eprintln!("rev: cannot ask rev who owns PID {}: {}", pid, e);
+            Policy::unowned()
+        }
+    }
+}
+
+/// The `coredump-handler` entry point the kernel runs, with the core on stdin.
+pub fn handle(pid: u32, signal: i32, timestamp: i64, comm: &str) {
+    let policy = lookup_policy(pid);
+    match store(
+        &cores_dir(),
+        &policy,
+        pid,
+        signal,
+        timestamp,
+        comm,
+        io::stdin().lock(),
+    ) {
+        Ok(record) => crate::logger::write_log(
+            record.service.as_deref().unwrap_or("rev"),
+            &format!(
+                "Core dumped by {} (PID {}, signal {}): {} bytes stored as {}{}",
+                comm,
+                pid,
+                signal,
+                record.size,
+                record.id,
+                if record.truncated { " (truncated)" } else { "" }
+            ),
+        ),
+        Err(e) => eprintln!("rev: cannot store core of PID {}: {}", pid, e),
+    }
+}
+
+/// Store a core read from `core` and its record under the cores directory
+/// `root`, then prune the service's oldest cores beyond its `keep` limit.
+fn store(
+    root: &Path,
+    policy: &Policy,
+    pid: u32,
+    signal: i32,
+    timestamp: i64,
+    comm: &str,
+    mut core: impl Read,
+) -> io::Result<CoreRecord> {
+    let dir = service_dir(root, policy.service.as_deref());
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)?;
+    let id = format!("{}-{}", timestamp, pid);
+
+    let mut size = 0;
+    let mut truncated = false;
+    if policy.size_max > 0 {
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(dir.join(format!("{}.core", id)))?;
+        size = io::copy(&mut (&mut core).take(policy.size_max), &mut file)?;
+        truncated = core.read(&mut [0u8; 1])? > 0;
+    }
+
+    let record = CoreRecord {
+        id: id.clone(),
+        dir: dir.clone(),
+        service: policy.service.clone(),
+        pid,
+        signal,
+        comm: comm.to_string(),
+        timestamp,
+        size,
+        truncated,
+    };
+    let text = toml::to_string(&record).map_err(io::Error::other)?;
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(dir.join(format!("{}.toml", id)))?
+        .write_all(text.as_bytes())?;
+
+    prune(&dir, policy.keep as usize);
+    Ok(record)
+}
+
+/// The records in one service directory, oldest first.
+fn read_dir(dir: &Path) -> Vec<CoreRecord> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut records: Vec<CoreRecord> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "toml" {
+                return None;
+            }
+            let mut record: CoreRecord = toml::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            record.id = path.file_stem()?.to_string_lossy().into_owned();
+            record.dir = dir.to_path_buf();
+            Some(record)
+        })
+        .collect();
+    records.sort_by_key(|r| (r.timestamp, r.pid));
+    records
+}
+
+/// Remove the oldest cores in `dir` beyond `keep`.
+fn prune(dir: &Path, keep: usize) {
+    let records = read_dir(dir);
+    let excess = records.len().saturating_sub(keep);
+    for record in &records[..excess] {
+        let _ = fs::remove_file(dir.join(format!("{}.core", record.id)));
+        let _ = fs::remove_file(dir.join(format!("{}.toml", record.id)));
+    }
+}
+
+/// Every stored core, or one service's, oldest first.
+pub fn list(service: Option<&str>) -> Vec<CoreRecord> {
+    list_in(&cores_dir(), service)
+}
+
+/// [`list`] for the cores directory `root`.
+fn list_in(root: &Path, service: Option<&str>) -> Vec<CoreRecord> {
+    if service.is_some() {
+        return read_dir(&service_dir(root, service));
+    }
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut records: Vec<CoreRecord> = entries
+        .flatten()
+        .flat_map(|entry| read_dir(&entry.path()))
+        .collect();
+    records.sort_by_key(|r| (r.timestamp, r.pid));
+    records
+}
+
+/// The stored core with this id.
+pub fn find(id: &str) -> Option<CoreRecord> {
+    find_in(&cores_dir(), id)
+}
+
+fn find_in(root: &Path, id: &str) -> Option<CoreRecord> {
+    list_in(root, None).into_iter().find(|r| r.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cores_are_truncated_and_pruned_per_service() {
+        let policy = Policy {
+            service: Some("com.test.coredump/crasher".to_string()),
+            size_max: 4,
+            keep: 2,
+        };
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        for (pid, core) in [(10, &b"ELF-core"[..]), (11, b"ELF"), (12, b"ELF!")] {
+            store(
+                root,
+                &policy,
+                pid,
+                libc::SIGSEGV,
+                1_700_000_000 + pid as i64,
+                "crasher",
+                core,
+            )
+            .unwrap();
+        }
+
+        let records = list_in(root, policy.service.as_deref());
+        let pids: Vec<u32> = records.iter().map(|r| r.pid).collect();
+        assert_eq!(pids, [11, 12]);
+        assert_eq!((records[0].size, records[0].truncated), (3, false));
+        assert_eq!((records[1].size, records[1].truncated), (4, false));
+        let found = find_in(root, &records[1].id).unwrap();
+        assert_eq!(fs::read(found.core_path().unwrap()).unwrap(), b"ELF!");
+
+        let big = store(
+            root,
+            &policy,
+            13,
+            libc::SIGABRT,
+            1_700_000_100,
+            "crasher",
+            &b"ELF-core"[..],
+        )
+        .unwrap();
+        assert_eq!((big.size, big.truncated), (4, true));
+        assert!(find_in(root, "1700000010-10").is_none());
+
+        // A name that only differs by the separator is a different service.
+        let other = Policy {
+            service: Some("com.test.coredump_crasher".to_string()),
+            ..policy
+        };
+        store(
+            root,
+            &other,
+            14,
+            libc::SIGSEGV,
+            1_700_000_200,
+            "crasher",
+            &b"ELF"[..],
+        )
+        .unwrap();
+        assert_eq!(list_in(root, other.service.as_deref()).len(), 1);
+        assert_eq!(list_in(root, Some("com.test.coredump/crasher")).len(), 2);
+    }
+}
@@ -0,0 +1,156 @@
+//! Secrets for services, from RookGuard (`credentials = [...]`).
+//!
+//! At each start rev fetches every named secret from rookd and writes it to a
+//! file of that name in the service's credentials directory,
+//! `/Transit/Ephemeral/rev/credentials/<service>/`. The directory is a ramfs of
+//! its own, so secrets never reach a disk or swap; it and the files are owned
+//! by the uid the service runs as and readable by nobody else. The service
+//! finds it through `CREDENTIALS_DIRECTORY`. Secret values are never put in the
+//! environment, on a command line or in a log. The directory is unmounted when
+//! the process exits; a restart fetches the secrets again.
+
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use nix::mount::{MntFlags, MsFlags, mount, umount2};
+
+use crate::parser::ServiceConfig;
+
+/// The environment variable pointing a service at its credentials.
+pub const ENV_VAR: &str = "CREDENTIALS_DIRECTORY";
+
+fn base() -> PathBuf {
+    if cfg!(debug_assertions) {
+        PathBuf::from("./Transit/Ephemeral/rev/credentials")
+    } else {
+        PathBuf::from("/Transit/Ephemeral/rev/credentials")
+    }
+}
+
+/// The credentials directory of service `name`. The name is escaped
+/// injectively, so no two services share a directory.
+pub fn dir(name: &str) -> PathBuf {
+    base().join(super::scheduler::file_name_for(name))
+}
+
+/// Whether `name` can be a file in the credentials directory.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+/// Fetch the service's secrets and install them for `owner` (uid, gid), or
+/// for root when it keeps rev's privileges. `None` when it asks for none. Any
+/// secret that cannot be fetched fails the whole start.
+pub fn prepare(
+    config: &ServiceConfig,
+    owner: Option<(u32, u32)>,
+) -> Result<Option<PathBuf>, String> {
+    if config.credentials.is_empty() {
+        return Ok(None);
+    }
+    if let Some(bad) = config.credentials.iter().find(|c| !valid_name(c)) {
+        return Err(format!("invalid credential name '{}'", bad));
+    }
+    // Secrets are held per account; a dynamic user has none of its own.
+    let account = match config.user.as_deref() {
+        Some(user) if !config.dynamic_user => user,
+        _ => "root",
+    };
+    let mut secrets = Vec::with_capacity(config.credentials.len());
+    for name in &config.credentials {
+        secrets.push((name.as_str(), crate::auth::fetch_secret(account, name)?));
+    }
+
+    let dir = dir(&config.name);
+    let installed = install(&dir, &secrets, owner);
+    for (_, value) in &mut secrets {
+        value.fill(0);
+    }
+    match installed {
+        Ok(()) => Ok(Some(dir)),
+        Err(e) => {
+            remove(&config.name);
+            Err(format!("{}: {}", dir.display(), e))
+        }
+    }
+}
+
+/// Write `secrets` into a fresh private directory at `dir`.
+fn install(dir: &Path, secrets: &[(&str, Vec<u8>)], owner: Option<(u32, u32)>) -> io::Result<()> {
+    clear(dir)?;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    if !cfg!(debug_assertions) {
+        mount(
+            Some("ramfs"),
+            dir,
+            Some("ramfs"),
+            MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC | MsFlags::MS_NODEV,
+            Some("mode=0700"),
+        )
+        .map_err(io::Error::from)?;
+    }
+    let (uid, gid) = owner.unzip();
+    for (name, value) in secrets.iter() {
+        let path = dir.join(name);
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o400)
+            .open(&path)?
+            .write_all(value)?;
+        std::os::unix::fs::chown(&path, uid, gid)?;
+    }
+    std::os::unix::fs::chown(dir, uid, gid)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o500))
+}
+
+/// Unmount and delete `dir`, if it is there.
+fn clear(dir: &Path) -> io::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    if !cfg!(debug_assertions) {
+        let _ = umount2(dir, MntFlags::MNT_DETACH);
+    }
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    fs::remove_dir_all(dir)
+}
+
+/// Drop the credentials of service `name`, whose process is gone.
+pub fn remove(name: &str) {
+    if let Err(e) = clear(&dir(name)) {
+        eprintln!("rev: cannot remove credentials of {}: {}", name, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn secrets_are_installed_privately() {
+        assert!(!valid_name("../shadow") && !valid_name("") && !valid_name(".."));
+        assert!(valid_name("db-password"));
+        assert_ne!(dir("com.test/creds"), dir("com.test_creds"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("com.test.creds");
+        let secrets = [("db-password", b"hunter2".to_vec())];
+        install(&dir, &secrets, None).unwrap();
+        // Installing again replaces the previous run's files.
+        install(&dir, &secrets, None).unwrap();
+
+        let file = dir.join("db-password");
+        assert_eq!(fs::read(&file).unwrap(), b"hunter2");
+        assert_eq!(fs::metadata(&file).unwrap().mode() & 0o777, 0o400);
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o500);
+        clear(&dir).unwrap();
+        assert!(!dir.exists());
+    }
+}
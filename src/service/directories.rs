@@ -0,0 +1,173 @@
+//! Directories rev creates for services: `state-directory`, `cache-directory`,
+//! `runtime-directory` and `logs-directory`.
+//!
+//! Each is created under its kind's base before `exec-start-pre` runs, owned
+//! by the uid/gid the service runs as and given its configured mode, and its
+//! path is exported to the service and its hooks. A directory whose owner does
+//! not match (a dynamic uid that changed, a `user =` that was edited) is
+//! re-owned with everything in it. The runtime directory is removed when the
+//! service stops; the others persist.
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::parser::{ManagedDir, ServiceConfig};
+
+/// The kinds of managed directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    State,
+    Cache,
+    Runtime,
+    Logs,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [Kind::State, Kind::Cache, Kind::Runtime, Kind::Logs];
+
+    /// Where directories of this kind are created.
+    pub fn base(self) -> PathBuf {
+        let base = match self {
+            Kind::State => "/Vault/State",
+            Kind::Cache => "/Transit/Volatile/cache",
+            Kind::Runtime => "/Transit/Ephemeral/run",
+            Kind::Logs => "/Vault/Chronicle",
+        };
+        if cfg!(debug_assertions) {
+            PathBuf::from(format!(".{}", base))
+        } else {
+            PathBuf::from(base)
+        }
+    }
+
+    /// The environment variable the directory's path is exported as.
+    pub fn env_var(self) -> &'static str {
+        match self {
+            Kind::State => "STATE_DIRECTORY",
+            Kind::Cache => "CACHE_DIRECTORY",
+            Kind::Runtime => "RUNTIME_DIRECTORY",
+            Kind::Logs => "LOGS_DIRECTORY",
+        }
+    }
+
+    fn of(self, config: &ServiceConfig) -> Option<&ManagedDir> {
+        match self {
+            Kind::State => config.state_directory.as_ref(),
+            Kind::Cache => config.cache_directory.as_ref(),
+            Kind::Runtime => config.runtime_directory.as_ref(),
+            Kind::Logs => config.logs_directory.as_ref(),
+        }
+    }
+}
+
+/// The directories `config` asks for, with their full paths.
+pub fn configured(config: &ServiceConfig) -> Vec<(Kind, &ManagedDir, PathBuf)> {
+    Kind::ALL
+        .into_iter()
+        .filter_map(|kind| {
+            let dir = kind.of(config)?;
+            Some((kind, dir, kind.base().join(&dir.name)))
+        })
+        .collect()
+}
+
+/// The environment variables pointing a service at its directories.
+pub fn env(config: &ServiceConfig) -> Vec<(&'static str, PathBuf)> {
+    configured(config)
+        .into_iter()
+        .map(|(kind, _, path)| (kind.env_var(), path))
+        .collect()
+}
+
+/// Create the directories of `config`, owned by `owner` (uid, gid) when the
+/// service drops privileges.
+pub fn prepare(config: &ServiceConfig, owner: Option<(u32, u32)>) -> io::Result<()> {
+    for (kind, dir, path) in configured(config) {
+        // rev's own logs and cores live in the logs base; no service may take
+        // them over.
+        if kind == Kind::Logs && Path::new(&dir.name).starts_with("rev") {
+            return Err(io::Error::other(format!(
+                "logs-directory '{}' is rev's own",
+                dir.name
+            )));
+        }
+        ensure(&path, dir.mode, owner)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    }
+    Ok(())
+}
+
+fn ensure(path: &Path, mode: u32, owner: Option<(u32, u32)>) -> io::Result<()> {
+    fs::create_dir_all(path)?;
+    if let Some((uid, gid)) = owner {
+        let meta = fs::symlink_metadata(path)?;
+        if (meta.uid(), meta.gid()) != (uid, gid) {
+            chown_tree(path, uid, gid)?;
+        }
+    }
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+/// Give `path` and everything below it to `uid`:`gid`. Symlinks are re-owned
+/// themselves, never followed.
+fn chown_tree(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    for entry in walkdir::WalkDir::new(path) {
+        let entry = entry.map_err(io::Error::other)?;
+        std::os::unix::fs::lchown(entry.path(), Some(uid), Some(gid))?;
+    }
+    Ok(())
+}
+
+/// Remove the runtime directory of a service that stopped.
+pub fn remove_runtime(config: &ServiceConfig) {
+    let Some(dir) = config.runtime_directory.as_ref() else {
+        return;
+    };
+    let path = Kind::Runtime.base().join(&dir.name);
+    if let Err(e) = fs::remove_dir_all(&path)
+        && e.kind() != io::ErrorKind::NotFound
+    {
+        eprintln!("rev: cannot remove {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directories_are_created_with_their_mode_and_names_stay_inside() {
+        let parse = |spec: &str| {
+            toml::from_str::<ServiceConfig>(&format!(
+                "name = \"com.test.dirs\"\nexec-start = \"/bin/true\"\n{}",
+                spec
+            ))
+        };
+        assert!(parse("state-directory = \"../etc\"").is_err());
+        assert!(parse("cache-directory = \"/etc\"").is_err());
+        assert!(parse("runtime-directory = \"\"").is_err());
+        let config = parse(
+            "state-directory = \"com.test.dirs\"\n\
+             runtime-directory = { name = \"com.test.dirs/sock\", mode = 0o750 }",
+        )
+        .unwrap();
+        assert_eq!(
+            env(&config),
+            [
+                ("STATE_DIRECTORY", Kind::State.base().join("com.test.dirs")),
+                (
+                    "RUNTIME_DIRECTORY",
+                    Kind::Runtime.base().join("com.test.dirs/sock")
+                ),
+            ]
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("a/b");
+        ensure(&path, 0o750, None).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o750);
+    }
+}
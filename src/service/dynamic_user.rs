@@ -0,0 +1,85 @@
+//! Dynamic users: ephemeral uids for services with `dynamic-user = true`.
+//!
+//! Instead of a UAC account per small daemon, such a service gets a uid (and
+//! the same gid) from [`UID_MIN`]..=[`UID_MAX`] when it starts, released again
+//! when its process exits. The table is rev's own and lives in memory. A
+//! service's first choice is derived from its name, so it usually gets the same
+//! uid every time it starts, across reboots too. A uid is never handed out while
+//! another service holds it or a UAC account owns it.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// First uid of the reserved range.
+pub const UID_MIN: u32 = 61184;
+/// Last uid of the reserved range.
+pub const UID_MAX: u32 = 65519;
+
+/// Service name -> uid it currently holds.
+static HELD: Lazy<Mutex<HashMap<String, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The uid a service tries first: a hash (FNV-1a) of its name into the range.
+fn preferred(name: &str) -> u32 {
+    let hash = name.bytes().fold(0x811c9dc5u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x01000193)
+    });
+    UID_MIN + hash % (UID_MAX - UID_MIN + 1)
+}
+
+/// Whether a UAC account already has `uid`. If UAC cannot be read, the range
+/// is assumed to be rev's alone.
+fn owned_by_account(uid: u32) -> bool {
+    uac_core::Uac::open()
+        .and_then(|uac| uac.name_by_uid(uid))
+        .is_ok_and(|name| name.is_some())
+}
+
+/// Allocate a uid for service `name`, or return the one it already holds.
+/// `None` when the range is exhausted.
+pub fn allocate(name: &str) -> Option<u32> {
+    allocate_with(name, owned_by_account)
+}
+
+fn allocate_with(name: &str, taken: impl Fn(u32) -> bool) -> Option<u32> {
+    let mut held = HELD.lock().expect("dynamic user table lock poisoned");
+    if let Some(&uid) = held.get(name) {
+        return Some(uid);
+    }
+    let span = UID_MAX - UID_MIN + 1;
+    let first = preferred(name) - UID_MIN;
+    let uid = (0..span)
+        .map(|i| UID_MIN + (first + i) % span)
+        .find(|uid| !held.values().any(|h| h == uid) && !taken(*uid))?;
+    held.insert(name.to_string(), uid);
+    Some(uid)
+}
+
+/// Release the uid service `name` holds, if any.
+pub fn release(name: &str) {
+    HELD.lock()
+        .expect("dynamic user table lock poisoned")
+        .remove(name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uids_are_stable_per_name_and_never_shared() {
+        let a = allocate_with("com.test.dyn/a", |_| false).unwrap();
+        assert!((UID_MIN..=UID_MAX).contains(&a));
+        assert_eq!(allocate_with("com.test.dyn/a", |_| false), Some(a));
+
+        // A uid held by a service, or owned by an account, is skipped.
+        let b = allocate_with("com.test.dyn/b", |uid| uid == preferred("com.test.dyn/b"));
+        assert_ne!(b, Some(preferred("com.test.dyn/b")));
+        assert_ne!(b, Some(a));
+
+        release("com.test.dyn/a");
+        assert_eq!(allocate_with("com.test.dyn/a", |_| false), Some(a));
+        release("com.test.dyn/a");
+        release("com.test.dyn/b");
+    }
+}
@@ -0,0 +1,119 @@
+//! Health checks (`health-check = { exec, interval, timeout, retries }`).
+//!
+//! While a service with a health check runs, a thread probes it every
+//! `interval` seconds by running `exec` the way hooks run, in the service's
+//! environment and working directory. A probe passes when it exits 0 within
+//! `timeout` seconds; one still running then is killed and fails. The verdict
+//! is kept on the service's entry in the table. After `retries` failures in a
+//! row the service is unhealthy: rev logs it and queues a restart job. The
+//! thread ends with the process it probes; the restarted process gets its own.
+
+use std::thread;
+
+use super::jobs::{self, JobKind};
+use super::supervisor;
+use crate::init::services;
+use crate::parser::{Health, HealthCheck, ServiceConfig, ServiceState};
+
+/// Probe the service `config` describes while its process `pid` runs.
+pub fn watch(config: &ServiceConfig, pid: u32) {
+    let config = config.clone();
+    thread::spawn(move || run(&config, pid));
+}
+
+fn run(config: &ServiceConfig, pid: u32) {
+    let Some(ref check) = config.health_check else {
+        return;
+    };
+    let mut tally = Tally::default();
+    loop {
+        thread::sleep(check.interval());
+        let running = services::get_service(&config.name)
+            .is_some_and(|i| i.pid == Some(pid) && i.state == ServiceState::Running);
+        if !running {
+            return;
+        }
+
+        let result = probe(check, config);
+        if let Err(ref failure) = result {
+            crate::logger::write_log(
+                &config.name,
+                &format!(
+                    "Health check failed ({}/{}): {}",
+                    tally.failures + 1,
+                    check.retries(),
+                    failure
+                ),
+            );
+        }
+        match tally.record(result.is_ok(), check.retries()) {
+            Some(Health::Unhealthy) => {
+                services::set_health(pid, Health::Unhealthy);
+                eprintln!("rev: {} is unhealthy, restarting it", config.name);
+                crate::logger::write_log(
+                    &config.name,
+                    &format!(
+                        "Unhealthy after {} failed health checks, restarting",
+                        tally.failures
+                    ),
+                );
+                jobs::enqueue(&config.name, JobKind::Restart);
+                return;
+            }
+            Some(health) => services::set_health(pid, health),
+            None => {}
+        }
+    }
+}
+
+/// Run the probe once.
+fn probe(check: &HealthCheck, config: &ServiceConfig) -> Result<(), String> {
+    let mut cmd = super::hook_command(&check.exec, config)?;
+    match supervisor::run_command_timeout(&mut cmd, check.timeout()) {
+        Ok(Some(status)) if status.success() => Ok(()),
+        Ok(Some(status)) => Err(super::describe_failure(status)),
+        Ok(None) => Err(format!("timed out after {}s", check.timeout().as_secs())),
+        Err(e) => Err(format!("could not run: {}", e)),
+    }
+}
+
+/// Consecutive probe failures of one run.
+#[derive(Default)]
+struct Tally {
+    failures: u32,
+}
+
+impl Tally {
+    /// Count a probe, returning the new verdict, or `None` while failures are
+    /// still below `retries` (the previous verdict stands).
+    fn record(&mut self, passed: bool, retries: u32) -> Option<Health> {
+        if passed {
+            self.failures = 0;
+            return Some(Health::Healthy);
+        }
+        self.failures += 1;
+        (self.failures >= retries).then_some(Health::Unhealthy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_consecutive_failures_make_unhealthy() {
+        let mut tally = Tally::default();
+        assert_eq!(tally.record(false, 3), None);
+        assert_eq!(tally.record(false, 3), None);
+        // A pass starts the count again.
+        assert_eq!(tally.record(true, 3), Some(Health::Healthy));
+        assert_eq!(tally.record(false, 3), None);
+        assert_eq!(tally.record(false, 3), None);
+        assert_eq!(tally.record(false, 3), Some(Health::Unhealthy));
+
+        let check: HealthCheck = toml::from_str("exec = \"/bin/true\"").unwrap();
+        assert_eq!(check.interval().as_secs(), 30);
+        assert_eq!(check.timeout().as_secs(), 5);
+        assert_eq!(check.retries(), 3);
+    }
+}
@@ -0,0 +1,111 @@
+//! Idle exit for bus-activated services (`idle-exit-sec`).
+//!
+//! rev sees a service's clients only when they look up one of the names it
+//! `provides`; after that they talk to it directly. So the Highway records when
+//! each name was last looked up, and while a service with `idle-exit-sec` runs,
+//! a thread checks it: once none of its names has been looked up for that long
+//! (counting from its start), rev stops it. That stop is a clean one, as if
+//! requested: the restart policy and `on-failure` do not apply. The next Lookup
+//! activates the service again.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use super::jobs::{self, JobKind};
+use crate::init::services;
+use crate::parser::ServiceConfig;
+
+/// When each registered Highway name was last looked up.
+static LAST_LOOKUP: Lazy<Mutex<HashMap<String, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Note a successful Highway Lookup of `bus_name`.
+pub fn looked_up(bus_name: &str) {
+    LAST_LOOKUP
+        .lock()
+        .expect("lookup times lock poisoned")
+        .insert(bus_name.to_string(), Instant::now());
+}
+
+/// Stop the service `config` describes once it is idle, while its process
+/// `pid` runs. Does nothing unless it sets `idle-exit-sec` and `provides` a
+/// name to be activated by again.
+pub fn watch(config: &ServiceConfig, pid: u32) {
+    let Some(idle_exit) = config.idle_exit() else {
+        return;
+    };
+    if config.provides.is_empty() {
+        return;
+    }
+    let name = config.name.clone();
+    let provides = config.provides.clone();
+    thread::spawn(move || run(&name, &provides, idle_exit, pid));
+}
+
+fn run(name: &str, provides: &[String], idle_exit: Duration, pid: u32) {
+    let started = Instant::now();
+    loop {
+        let running = services::get_service(name).is_some_and(|i| i.pid == Some(pid));
+        if !running {
+            return;
+        }
+        let idle = {
+            let lookups = LAST_LOOKUP.lock().expect("lookup times lock poisoned");
+            last_use(&lookups, provides, started).elapsed()
+        };
+        if idle < idle_exit {
+            thread::sleep(idle_exit - idle);
+            continue;
+        }
+        println!("rev: {} idle for {}s, stopping", name, idle.as_secs());
+        crate::logger::write_log(
+            name,
+            &format!("No lookups for {}s, stopping (idle-exit)", idle.as_secs()),
+        );
+        jobs::enqueue(name, JobKind::Stop);
+        return;
+    }
+}
+
+/// The latest lookup of any of `provides`, or `since` if none came after it.
+fn last_use(lookups: &HashMap<String, Instant>, provides: &[String], since: Instant) -> Instant {
+    provides
+        .iter()
+        .filter_map(|p| lookups.get(p).copied())
+        .fold(since, Instant::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_latest_lookup_of_any_name_counts() {
+        let start = Instant::now();
+        let provides = ["a.b.thumbs".to_string(), "a.b.thumbs2".to_string()];
+        let mut lookups = HashMap::new();
+        assert_eq!(last_use(&lookups, &provides, start), start);
+
+        let later = start + Duration::from_secs(5);
+        lookups.insert("a.b.thumbs2".to_string(), later);
+        lookups.insert("other.name".to_string(), later + Duration::from_secs(5));
+        assert_eq!(last_use(&lookups, &provides, start), later);
+        // A lookup from before this run started does not count.
+        lookups.insert("a.b.thumbs".to_string(), start);
+        assert_eq!(last_use(&lookups, &provides, later), later);
+    }
+
+    #[test]
+    fn a_zero_idle_exit_still_waits_a_second() {
+        let mut config = ServiceConfig::default();
+        assert_eq!(config.idle_exit(), None);
+        config.idle_exit_sec = Some(0);
+        assert_eq!(config.idle_exit(), Some(Duration::from_secs(1)));
+        config.idle_exit_sec = Some(300);
+        assert_eq!(config.idle_exit(), Some(Duration::from_secs(300)));
+    }
+}
@@ -0,0 +1,321 @@
+//! The job engine: queued start/stop/restart/reload transactions.
+//!
+//! A control request from the bus no longer runs inside the bus handler. It
+//! becomes a job with an id, the reply carries that id at once, and the job
+//! runs on its own thread. When it finishes rev emits a `JobFinished` signal
+//! (source `rev`) whose payload is a MessagePack [`JobResult`], so a caller
+//! that wants the outcome (`rev start --wait`) subscribes to it.
+//!
+//! Each service has at most one running job and one pending job. A request
+//! that arrives while a job runs is merged into, or replaces, the pending one:
+//!
+//! - the same kind as the pending job joins it (both callers get its id);
+//! - a pending start or restart absorbs a start, restart or reload, since a
+//!   freshly started process covers all three;
+//! - anything else (stop after start, start after stop, ...) cancels the
+//!   pending job, which finishes as `cancelled`, and takes its place.
+//!
+//! A start or stop identical to the running job, with nothing pending, joins
+//! the running job. A restart or reload does not: it was asked for after the
+//! running one began, so it must observe the state that one leaves behind.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use crate::bus::protocol::JobResult;
+use crate::init::services;
+
+/// What a job does to its service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobKind {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+}
+
+impl JobKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobKind::Start => "start",
+            JobKind::Stop => "stop",
+            JobKind::Restart => "restart",
+            JobKind::Reload => "reload",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Job {
+    id: u64,
+    kind: JobKind,
+}
+
+/// The jobs of one service.
+#[derive(Debug, Default)]
+struct Slot {
+    running: Option<Job>,
+    pending: Option<Job>,
+}
+
+/// What [`Slot::enqueue`] decided.
+#[derive(Debug, PartialEq, Eq)]
+enum Queued {
+    /// The slot was idle: run this job now.
+    Run(Job),
+    /// Joined an existing job with this id.
+    Joined(u64),
+    /// Became the pending job, replacing (cancelling) the previous one.
+    Pending(Job, Option<Job>),
+}
+
+impl Slot {
+    fn enqueue(&mut self, kind: JobKind, id: u64) -> Queued {
+        let job = Job { id, kind };
+        let Some(running) = self.running else {
+            self.running = Some(job);
+            return Queued::Run(job);
+        };
+        match self.pending {
+            None if running.kind == kind && matches!(kind, JobKind::Start | JobKind::Stop) => {
+                Queued::Joined(running.id)
+            }
+            Some(pending) if absorbs(pending.kind, kind) => Queued::Joined(pending.id),
+            cancelled => {
+                self.pending = Some(job);
+                Queued::Pending(job, cancelled)
+            }
+        }
+    }
+}
+
+/// Whether a pending job of kind `pending` already satisfies a new `new`.
+fn absorbs(pending: JobKind, new: JobKind) -> bool {
+    pending == new
+        || (matches!(pending, JobKind::Start | JobKind::Restart)
+            && matches!(new, JobKind::Start | JobKind::Restart | JobKind::Reload))
+}
+
+struct Jobs {
+    next_id: u64,
+    slots: HashMap<String, Slot>,
+}
+
+static JOBS: Lazy<Mutex<Jobs>> = Lazy::new(|| {
+    Mutex::new(Jobs {
+        next_id: 1,
+        slots: HashMap::new(),
+    })
+});
+
+/// Queue a job for `service` and return its id (an existing job's id if the
+/// request was merged). The job runs in the background; its outcome arrives as
+/// a `JobFinished` signal.
+pub fn enqueue(service: &str, kind: JobKind) -> u64 {
+    let queued = {
+        let mut jobs = JOBS.lock().expect("job table lock poisoned");
+        let id = jobs.next_id;
+        let queued = jobs
+            .slots
+            .entry(service.to_string())
+            .or_default()
+            .enqueue(kind, id);
+        if !matches!(queued, Queued::Joined(_)) {
+            jobs.next_id += 1;
+        }
+        queued
+    };
+    match queued {
+        Queued::Run(job) => {
+            let service = service.to_string();
+            thread::spawn(move || run_jobs(service, job));
+            job.id
+        }
+        Queued::Joined(id) => id,
+        Queued::Pending(job, cancelled) => {
+            if let Some(old) = cancelled {
+                finish(
+                    service,
+                    old,
+                    "cancelled",
+                    format!("superseded by job {}", job.id),
+                );
+            }
+            job.id
+        }
+    }
+}
+
+/// Block until `service` has no running or pending job.
+pub fn wait_idle(service: &str) {
+    while JOBS
+        .lock()
+        .expect("job table lock poisoned")
+        .slots
+        .contains_key(service)
+    {
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Run `job`, then whatever became pending meanwhile, until the slot is idle.
+fn run_jobs(service: String, mut job: Job) {
+    loop {
+        crate::logger::write_log(
+            &service,
+            &format!("Job {} ({}) started", job.id, job.kind.as_str()),
+        );
+        match execute(&service, job.kind) {
+            Ok(message) => finish(&service, job, "done", message),
+            Err(message) => finish(&service, job, "failed", message),
+        }
+        let mut jobs = JOBS.lock().expect("job table lock poisoned");
+        let slot = jobs.slots.entry(service.clone()).or_default();
+        match slot.pending.take() {
+            Some(next) => {
+                slot.running = Some(next);
+                job = next;
+            }
+            None => {
+                jobs.slots.remove(&service);
+                return;
+            }
+        }
+    }
+}
+
+fn finish(service: &str, job: Job, result: &str, message: String) {
+    crate::logger::write_log(
+        service,
+        &format!(
+            "Job {} ({}) {}: {}",
+            job.id,
+            job.kind.as_str(),
+            result,
+            message
+        ),
+    );
+    let payload = JobResult {
+        job_id: job.id,
+        service: service.to_string(),
+        kind: job.kind.as_str().to_string(),
+        result: result.to_string(),
+        message,
+    };
+    crate::bus::signals::emit("JobFinished", &payload);
+}
+
+fn execute(service: &str, kind: JobKind) -> Result<String, String> {
+    match kind {
+        JobKind::Start => start(service),
+        JobKind::Stop => stop(service),
+        JobKind::Restart => {
+            stop(service)?;
+            start(service)
+        }
+        JobKind::Reload => reload(service),
+    }
+}
+
+fn start(name: &str) -> Result<String, String> {
+    match services::get_service(name) {
+        Some(info) if info.is_running => Ok(format!("{} is already running", name)),
+        Some(_) => {
+            if super::start_known_service(name) {
+                Ok(format!("Started service: {}", name))
+            } else {
+                Err(format!("service '{}' failed to start", name))
+            }
+        }
+        // Not loaded yet: start it from its app's service directory.
+        None => {
+            let (app_id, _service, file) = crate::cli::parse_service::parse_service(name)
+                .map_err(|e| format!("invalid service name: {}", e))?;
+            let filename = file.file_name().ok_or("invalid service file path")?;
+            let path = std::path::PathBuf::from(format!("./Services/{}", app_id)).join(filename);
+            super::start_service_from_path(&path);
+            Ok(format!("Started service: {}", name))
+        }
+    }
+}
+
+/// How long a stop waits for the reaper to record the exit, so a following
+/// start sees the service as stopped rather than still running.
+const EXIT_SETTLE: Duration = Duration::from_secs(2);
+
+fn stop(name: &str) -> Result<String, String> {
+    let info =
+        services::get_service(name).ok_or_else(|| format!("service '{}' not found", name))?;
+    let Some(pid) = info.pid.filter(|_| info.is_running) else {
+        return Ok(format!("{} is not running", name));
+    };
+    super::stop_service(&info);
+    let deadline = Instant::now() + EXIT_SETTLE;
+    while Instant::now() < deadline
+        && services::get_service(name).is_some_and(|i| i.is_running && i.pid == Some(pid))
+    {
+        thread::sleep(Duration::from_millis(50));
+    }
+    Ok(format!("Stopped service: {}", name))
+}
+
+fn reload(name: &str) -> Result<String, String> {
+    let info =
+        services::get_service(name).ok_or_else(|| format!("service '{}' not found", name))?;
+    let pid = info
+        .pid
+        .ok_or_else(|| format!("service '{}' is not running", name))?;
+    if let Some(ref reload_cmd) = info.config.exec_reload {
+        if super::run_hook("exec-reload", reload_cmd, &info.config) {
+            Ok(format!("Reloaded service: {}", name))
+        } else {
+            Err(format!("exec-reload failed for {}", name))
+        }
+    } else {
+        // Fallback: send SIGHUP
+        super::supervisor::signal(pid, nix::sys::signal::Signal::SIGHUP);
+        Ok(format!("Sent SIGHUP to service: {}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: u64, kind: JobKind) -> Job {
+        Job { id, kind }
+    }
+
+    #[test]
+    fn requests_merge_into_or_cancel_pending_jobs() {
+        let mut slot = Slot::default();
+        assert_eq!(
+            slot.enqueue(JobKind::Start, 1),
+            Queued::Run(job(1, JobKind::Start))
+        );
+        // A second start while the first runs joins it.
+        assert_eq!(slot.enqueue(JobKind::Start, 2), Queued::Joined(1));
+        // A reload must see the started process, so it waits.
+        assert_eq!(
+            slot.enqueue(JobKind::Reload, 3),
+            Queued::Pending(job(3, JobKind::Reload), None)
+        );
+        // A stop replaces the pending reload, which is cancelled.
+        assert_eq!(
+            slot.enqueue(JobKind::Stop, 4),
+            Queued::Pending(job(4, JobKind::Stop), Some(job(3, JobKind::Reload)))
+        );
+        assert_eq!(slot.enqueue(JobKind::Stop, 5), Queued::Joined(4));
+        // A restart cancels the stop; a later start or reload is absorbed.
+        assert_eq!(
+            slot.enqueue(JobKind::Restart, 6),
+            Queued::Pending(job(6, JobKind::Restart), Some(job(4, JobKind::Stop)))
+        );
+        assert_eq!(slot.enqueue(JobKind::Start, 7), Queued::Joined(6));
+        assert_eq!(slot.enqueue(JobKind::Reload, 8), Queued::Joined(6));
+    }
+}
@@ -0,0 +1,198 @@
+//! Memory pressure and OOM kill handling for services with a cgroup.
+//!
+//! While a service runs, a thread watches its cgroup: `memory.pressure` (PSI)
+//! with a trigger, and `memory.events` through inotify. Pressure emits a
+//! `MemoryPressure` signal and runs the service's `memory-pressure-action`, so
+//! the desktop can react before the kernel has to kill anything. An OOM kill
+//! of one of its processes emits `OomKilled` and, while the main process lives
+//! on, applies `oom-policy`. An OOM kill of the main process itself is
+//! attributed when it exits ([`killed_by_oom`]).
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+use super::jobs::{self, JobKind};
+use super::{cgroup, supervisor};
+use crate::bus::protocol::{MemoryPressure, OomKill};
+use crate::init::services;
+use crate::parser::OomPolicy;
+
+/// PSI trigger: some task stalled on memory for 150ms within a 1s window.
+const PRESSURE_TRIGGER: &str = "some 150000 1000000";
+
+/// Minimum time between two runs of a service's `memory-pressure-action`.
+const ACTION_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Whether `exit` was the OOM killer's: a SIGKILL while the cgroup's OOM kill
+/// count rose above `baseline`, its count when the run started.
+pub fn killed_by_oom(name: &str, exit: supervisor::Exit, baseline: Option<u64>) -> bool {
+    matches!(
+        exit,
+        supervisor::Exit::Signal {
+            signal: libc::SIGKILL,
+            ..
+        }
+    ) && cgroup::oom_kills(name) > baseline
+}
+
+/// Watch the cgroup of service `name` until its main process `pid` exits.
+pub fn watch(name: &str, pid: u32, cgroup: PathBuf) {
+    let name = name.to_string();
+    thread::spawn(move || run(&name, pid, &cgroup));
+}
+
+fn open_trigger(cgroup: &Path) -> io::Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(cgroup.join("memory.pressure"))?;
+    file.write_all(PRESSURE_TRIGGER.as_bytes())?;
+    Ok(file)
+}
+
+fn run(name: &str, pid: u32, cgroup: &Path) {
+    let pressure = open_trigger(cgroup)
+        .map_err(|e| eprintln!("rev: no memory pressure trigger for {}: {}", name, e))
+        .ok();
+    let events = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+        .and_then(|i| {
+            i.add_watch(&cgroup.join("memory.events"), AddWatchFlags::IN_MODIFY)?;
+            Ok(i)
+        })
+        .map_err(|e| eprintln!("rev: cannot watch memory.events of {}: {}", name, e))
+        .ok();
+    if pressure.is_none() && events.is_none() {
+        return;
+    }
+
+    let mut oom_kills = cgroup::oom_kills(name).unwrap_or(0);
+    let mut last_action: Option<Instant> = None;
+    let mut fds = [
+        libc::pollfd {
+            fd: pressure.as_ref().map_or(-1, |f| f.as_raw_fd()),
+            events: libc::POLLPRI,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: events.as_ref().map_or(-1, |i| i.as_fd().as_raw_fd()),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+    // Poll with a timeout so the thread ends soon after the process does.
+    while !supervisor::wait_exit(pid, Duration::ZERO) {
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, 1000) } <= 0 {
+            continue;
+        }
+        if fds[0].revents & libc::POLLERR != 0 {
+            // The cgroup is gone.
+            return;
+        }
+        if fds[0].revents & libc::POLLPRI != 0 {
+            on_pressure(name, cgroup, &mut last_action);
+        }
+        if fds[1].revents & libc::POLLIN != 0 {
+            if let Some(ref inotify) = events {
+                let _ = inotify.read_events();
+            }
+            let now = cgroup::oom_kills(name).unwrap_or(oom_kills);
+            if now > oom_kills {
+                oom_kills = now;
+                on_oom_kill(name, pid, now);
+            }
+        }
+    }
+}
+
+/// `(some avg10, full avg10)` from a PSI file.
+fn avg10(text: &str) -> (f64, f64) {
+    let line_avg = |kind: &str| {
+        text.lines()
+            .find(|l| l.starts_with(kind))
+            .and_then(|l| l.split_whitespace().find_map(|f| f.strip_prefix("avg10=")))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0.0)
+    };
+    (line_avg("some"), line_avg("full"))
+}
+
+fn on_pressure(name: &str, cgroup: &Path, last_action: &mut Option<Instant>) {
+    let (some_avg10, full_avg10) =
+        avg10(&std::fs::read_to_string(cgroup.join("memory.pressure")).unwrap_or_default());
+    crate::logger::write_log(
+        name,
+        &format!(
+            "Memory pressure: some avg10={:.2} full avg10={:.2}",
+            some_avg10, full_avg10
+        ),
+    );
+    crate::bus::signals::emit(
+        "MemoryPressure",
+        &MemoryPressure {
+            service: name.to_string(),
+            some_avg10,
+            full_avg10,
+            memory_bytes: cgroup::read_u64(cgroup, "memory.current"),
+        },
+    );
+
+    if last_action.is_some_and(|t| t.elapsed() < ACTION_COOLDOWN) {
+        return;
+    }
+    if let Some(info) = services::get_service(name)
+        && let Some(ref action) = info.config.memory_pressure_action
+    {
+        *last_action = Some(Instant::now());
+        super::run_hook("memory-pressure-action", action, &info.config);
+    }
+}
+
+fn on_oom_kill(name: &str, pid: u32, oom_kills: u64) {
+    eprintln!("rev: OOM killer killed a process of {}", name);
+    crate::logger::write_log(
+        name,
+        &format!("OOM killer killed a process ({} so far)", oom_kills),
+    );
+    crate::bus::signals::emit(
+        "OomKilled",
+        &OomKill {
+            service: name.to_string(),
+            oom_kills,
+        },
+    );
+    // If it took the main process, the exit handler applies the policy.
+    if supervisor::wait_exit(pid, Duration::from_millis(200)) {
+        return;
+    }
+    let Some(info) = services::get_service(name) else {
+        return;
+    };
+    let kind = match info.config.oom_policy {
+        OomPolicy::Stop => JobKind::Stop,
+        OomPolicy::Restart => JobKind::Restart,
+        OomPolicy::Continue => return,
+    };
+    crate::logger::write_log(name, &format!("Applying oom-policy: {}", kind.as_str()));
+    jobs::enqueue(name, kind);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pressure_averages_are_read() {
+        let psi = "some avg10=12.50 avg60=3.10 avg300=0.70 total=123456\n\
+                   full avg10=4.00 avg60=1.00 avg300=0.20 total=45678\n";
+        assert_eq!(avg10(psi), (12.5, 4.0));
+        assert_eq!(avg10(""), (0.0, 0.0));
+    }
+}
@@ -1,137 +1,338 @@
+pub mod accounting;
+pub mod cgroup;
+pub mod coredump;
+pub mod credentials;
+pub mod directories;
+pub mod dynamic_user;
+pub mod health;
+pub mod idle;
+pub mod jobs;
+pub mod memory;
+pub mod paths;
+pub mod schedule_history;
 pub mod scheduler;
-
-use nix::sys::wait::{WaitPidFlag, WaitStatus, waitpid};
-use signal_hook::consts::signal::SIGCHLD;
-use signal_hook::iterator::Signals;
+pub mod supervisor;
+pub mod transient;
+pub mod user_services;
+
+use nix::sys::signal::Signal;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
 use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::init::services;
-use crate::parser::{RestartPolicy, ServiceConfig, ServiceInfo};
-
-/// Spawn a background thread that reaps zombie processes and handles
-/// restart policies when services exit.
-pub fn reap_zombies_loop() {
-    let mut signals = Signals::new(&[SIGCHLD]).expect("failed to register SIGCHLD handler");
-
-    thread::spawn(move || {
-        for _ in signals.forever() {
-            loop {
-                match waitpid(nix::unistd::Pid::from_raw(-1), Some(WaitPidFlag::WNOHANG)) {
-                    Ok(WaitStatus::StillAlive) => break,
-                    Ok(WaitStatus::Exited(pid, status)) => {
-                        println!("rev: child {} exited with status {}", pid, status);
-                        handle_exit(pid.as_raw() as u32, Some(status));
-                    }
-                    Ok(WaitStatus::Signaled(pid, signal, _core_dumped)) => {
-                        println!("rev: child {} killed by signal {:?}", pid, signal);
-                        handle_exit(pid.as_raw() as u32, None);
-                    }
-                    Ok(_) => {}
-                    Err(nix::errno::Errno::ECHILD) => break,
-                    Err(e) => {
-                        eprintln!("rev: waitpid error: {}", e);
-                        break;
-                    }
-                }
-            }
-        }
-    });
-}
-
-/// Called when a child process exits. Updates service state and
-/// handles restart policy.
-fn handle_exit(pid: u32, exit_code: Option<i32>) {
-    // Check if this was a session process
-    crate::session::handle_session_exit(pid);
+use crate::parser::{OomPolicy, RestartPolicy, ServiceConfig, ServiceInfo, ServiceState};
 
-    // Get the service info before updating (need it for restart decision)
-    let service_info = services::get_service_by_pid(pid);
-
-    // Update the service status to stopped
-    services::mark_service_exited(pid, exit_code);
+/// Called when a service's process exits. Updates service state and
+/// handles the OOM and restart policies.
+fn handle_exit(pid: u32, exit: supervisor::Exit, oom_killed: bool) {
+    let exit_code = exit.code();
+    // Update the service status to stopped, getting the service as it was
+    // (need it for the restart decision)
+    let service_info = services::mark_service_exited(pid, exit, oom_killed);
 
     // Handle restart policy
     if let Some(info) = service_info {
-        let should_restart = match info.config.restart_policy {
-            RestartPolicy::Always => true,
-            RestartPolicy::OnFailure => exit_code.is_none_or(|c| c != 0),
-            RestartPolicy::Never => false,
-            RestartPolicy::OnResourceChange => false, // TODO: cgroup monitoring
-        };
+        dynamic_user::release(&info.name);
+        credentials::remove(&info.name);
+        scheduler::service_stopped(&info.name, pid, exit_code);
+
+        // A process that was asked to stop stays stopped, whatever the policy.
+        // After an OOM kill, oom-policy decides unless it defers.
+        let mut should_restart = info.state != ServiceState::Stopping
+            && match (oom_killed, info.config.oom_policy) {
+                (true, OomPolicy::Stop) => false,
+                (true, OomPolicy::Restart) => true,
+                _ => match info.config.restart_policy {
+                    RestartPolicy::Always => true,
+                    RestartPolicy::OnFailure => exit_code.is_none_or(|c| c != 0),
+                    RestartPolicy::Never => false,
+                    RestartPolicy::OnResourceChange => false, // TODO: cgroup monitoring
+                },
+            };
+        if should_restart && !restart_allowed(&info.config) {
+            let (burst, interval) = info.config.start_limit();
+            eprintln!("rev: {} restarts too often, giving up", info.name);
+            crate::logger::write_log(
+                &info.name,
+                &format!(
+                    "Start limit hit ({} restarts within {}s), not restarting",
+                    burst,
+                    interval.as_secs()
+                ),
+            );
+            should_restart = false;
+        }
 
         if should_restart {
-            if let Some(ref config_path) = info.config_path {
-                let path = std::path::PathBuf::from(config_path);
+            if info.config_path.is_some() || info.transient {
                 println!(
                     "rev: restarting {} (policy: {:?})",
                     info.name, info.config.restart_policy
                 );
-                crate::logger::write_log(
-                    &info.name,
-                    &format!(
-                        "Service exited (code: {:?}), restarting per {:?} policy",
-                        exit_code, info.config.restart_policy
-                    ),
-                );
+                let reason = if oom_killed {
+                    format!(
+                        "previous run oom-kill, oom policy {:?}",
+                        info.config.oom_policy
+                    )
+                } else {
+                    format!(
+                        "previous run {}, restart policy {:?}",
+                        exit.describe(),
+                        info.config.restart_policy
+                    )
+                };
+                crate::logger::write_log(&info.name, &format!("Restarting: {}", reason));
                 // Small delay to avoid tight restart loops
                 thread::sleep(std::time::Duration::from_millis(500));
-                // Deregister so start_service_from_path can re-register
-                services::deregister_service(&info.name);
-                start_service_from_path(&path);
                 // Increment restart count
                 services::increment_restart_count(&info.name);
+                // The registration (and so the count) is kept; reloads keep its
+                // config current with the file.
+                if !start_registered(&info.name, Some(&reason)) {
+                    if info.transient {
+                        services::deregister_service(&info.name);
+                        cgroup::remove(&info.name);
+                    }
+                    // The restart failed, so the policy has given up on it.
+                    if exit.code() != Some(0) {
+                        run_triggers(&info, exit);
+                    }
+                }
             }
         } else {
             // Run exec-stop-post hook if defined
             if let Some(ref hook) = info.config.exec_stop_post {
-                run_hook(hook, &info.config);
+                run_hook("exec-stop-post", hook, &info.config);
+            }
+            directories::remove_runtime(&info.config);
+            // A path-triggered service whose condition still holds runs again.
+            paths::service_exited(&info, exit_code);
+            // A requested stop is neither a success nor a failure.
+            if info.state != ServiceState::Stopping {
+                run_triggers(&info, exit);
             }
         }
+
+        // A service whose file was removed by a reload while it was still
+        // running has no config path left (so it was not restarted above); it
+        // leaves the table with its process. So does a transient service that
+        // is done.
+        if info.config_path.is_none() && !(info.transient && should_restart) {
+            services::deregister_service(&info.name);
+            cgroup::remove(&info.name);
+        }
     }
 }
 
-/// Run a shell command as a hook (exec-start-pre, exec-start-post, etc.)
-/// Blocks until the command finishes. Returns true on success.
-pub fn run_hook(command: &str, config: &ServiceConfig) -> bool {
-    let args = match shell_words::split(command) {
-        Ok(a) if !a.is_empty() => a,
-        Ok(_) => {
-            eprintln!("rev: empty hook command");
-            return false;
+/// Recent policy restarts of each service, for its start limit.
+static RESTARTS: Lazy<Mutex<HashMap<String, Vec<Instant>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Count a policy restart of `config`'s service now, or return false if that
+/// would exceed its start limit. Giving up clears the count, so a later manual
+/// start gets a full allowance again.
+fn restart_allowed(config: &ServiceConfig) -> bool {
+    let mut restarts = RESTARTS.lock().expect("restarts lock poisoned");
+    let times = restarts.entry(config.name.clone()).or_default();
+    let allowed = within_limit(times, Instant::now(), config.start_limit());
+    if !allowed {
+        restarts.remove(&config.name);
+    }
+    allowed
+}
+
+/// Record a restart at `now` in `times` unless `burst` restarts already
+/// happened within `interval` of it.
+fn within_limit(
+    times: &mut Vec<Instant>,
+    now: Instant,
+    (burst, interval): (u32, Duration),
+) -> bool {
+    times.retain(|t| now.duration_since(*t) < interval);
+    if times.len() >= burst as usize {
+        return false;
+    }
+    times.push(now);
+    true
+}
+
+/// Start the `on-success` or `on-failure` services of `info`, whose run ended
+/// with `exit` and will not be restarted.
+fn run_triggers(info: &ServiceInfo, exit: supervisor::Exit) {
+    let (trigger, targets) = if exit.code() == Some(0) {
+        ("on-success", &info.config.on_success)
+    } else {
+        ("on-failure", &info.config.on_failure)
+    };
+    for target in targets {
+        let target = expand_target(target, &info.name);
+        let (mut config, instance) = match services::get_service(&target) {
+            Some(target_info) if target_info.is_running => {
+                println!(
+                    "rev: {} service {} of {} is already running",
+                    trigger, target, info.name
+                );
+                continue;
+            }
+            Some(target_info) => (target_info.config, false),
+            // An instance of a template runs the template's definition under
+            // its own name, as a transient entry that leaves with its process.
+            None => match template_of(&target).and_then(services::get_service) {
+                Some(template) => {
+                    let mut config = template.config;
+                    config.name = target.clone();
+                    let registered = services::register_new(
+                        target.clone(),
+                        ServiceInfo {
+                            name: target.clone(),
+                            transient: true,
+                            config: config.clone(),
+                            ..Default::default()
+                        },
+                    );
+                    if !registered {
+                        println!(
+                            "rev: {} service {} of {} is already running",
+                            trigger, target, info.name
+                        );
+                        continue;
+                    }
+                    (config, true)
+                }
+                None => {
+                    eprintln!(
+                        "rev: {} service {} of {} is not loaded",
+                        trigger, target, info.name
+                    );
+                    continue;
+                }
+            },
+        };
+        crate::logger::write_log(
+            &target,
+            &format!(
+                "Started by {} of {} ({})",
+                trigger,
+                info.name,
+                exit.describe()
+            ),
+        );
+        // The trigger's details go to this run only, not into the table.
+        config.env.0.extend(trigger_env(trigger, &info.name, exit));
+        if spawn_running(&config, None) != Spawn::Launched && instance {
+            services::deregister_service(&target);
+            cgroup::remove(&target);
         }
-        Err(e) => {
-            eprintln!("rev: failed to parse hook command '{}': {}", command, e);
-            return false;
+    }
+}
+
+/// A trigger target of `service` with `%n` replaced by the service's name.
+fn expand_target(target: &str, service: &str) -> String {
+    target.replace("%n", service)
+}
+
+/// The template `name@` that the instance `name@instance` runs.
+fn template_of(target: &str) -> Option<&str> {
+    let at = target.rfind('@')?;
+    (at + 1 < target.len()).then(|| &target[..=at])
+}
+
+/// The first `on-success` or `on-failure` target of `config` that names
+/// neither a loaded service nor an instance of a loaded template, as judged by
+/// `is_loaded`. A config with such a target is refused when it is loaded, so
+/// the mistake shows then rather than when the trigger fires.
+pub fn unresolved_trigger(
+    config: &ServiceConfig,
+    is_loaded: impl Fn(&str) -> bool,
+) -> Option<String> {
+    config
+        .on_failure
+        .iter()
+        .chain(&config.on_success)
+        .find(|target| {
+            let target = expand_target(target, &config.name);
+            !is_loaded(&target) && !template_of(&target).is_some_and(&is_loaded)
+        })
+        .cloned()
+}
+
+/// What a triggered service is told about the run that triggered it.
+fn trigger_env(trigger: &str, service: &str, exit: supervisor::Exit) -> Vec<(String, String)> {
+    let mut env = vec![
+        ("REV_TRIGGER".to_string(), trigger.to_string()),
+        ("REV_TRIGGER_SERVICE".to_string(), service.to_string()),
+        ("REV_TRIGGER_EXIT_STATUS".to_string(), exit.describe()),
+    ];
+    match exit {
+        supervisor::Exit::Code(code) => {
+            env.push(("REV_TRIGGER_EXIT_CODE".to_string(), code.to_string()))
+        }
+        supervisor::Exit::Signal { signal, .. } => {
+            env.push(("REV_TRIGGER_EXIT_SIGNAL".to_string(), signal.to_string()))
         }
+    }
+    env
+}
+
+/// Run a shell command as a hook (`hook` names it: exec-start-pre,
+/// exec-start-post, etc.). Blocks until the command finishes. Returns true on
+/// success; a failure is also recorded in the service's run history.
+pub fn run_hook(hook: &str, command: &str, config: &ServiceConfig) -> bool {
+    let failure = match run_hook_command(command, config) {
+        Ok(()) => return true,
+        Err(failure) => failure,
+    };
+    eprintln!(
+        "rev: {} hook '{}' for {} {}",
+        hook, command, config.name, failure
+    );
+    services::record_hook_failure(&config.name, format!("{}: {}", hook, failure));
+    false
+}
+
+/// Run a hook command, describing how it failed.
+fn run_hook_command(command: &str, config: &ServiceConfig) -> Result<(), String> {
+    let mut cmd = hook_command(command, config)?;
+    match supervisor::run_command(&mut cmd) {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(describe_failure(status)),
+        Err(e) => Err(format!("could not run: {}", e)),
+    }
+}
+
+/// How a hook or probe that did not succeed ended.
+fn describe_failure(status: std::process::ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("exited {}", code),
+        None => "was killed by a signal".to_string(),
+    }
+}
+
+/// A hook command set up to run in the service's environment and working
+/// directory.
+fn hook_command(command: &str, config: &ServiceConfig) -> Result<std::process::Command, String> {
+    let args = match shell_words::split(command) {
+        Ok(a) if !a.is_empty() => a,
+        Ok(_) => return Err("is empty".to_string()),
+        Err(e) => return Err(format!("could not be parsed: {}", e)),
     };
 
     let mut cmd = std::process::Command::new(&args[0]);
     cmd.args(&args[1..]);
 
-    // Inherit environment
+    // Inherit environment: rev.toml's defaults, then the service's
+    cmd.envs(&crate::settings::get().env);
     for (key, value) in &config.env {
         cmd.env(key, value);
     }
+    cmd.envs(directories::env(config));
     if let Some(ref dir) = config.working_dir {
         cmd.current_dir(dir);
     }
-
-    match cmd.status() {
-        Ok(status) => {
-            if !status.success() {
-                eprintln!(
-                    "rev: hook '{}' failed with exit code {:?}",
-                    command,
-                    status.code()
-                );
-            }
-            status.success()
-        }
-        Err(e) => {
-            eprintln!("rev: failed to run hook '{}': {}", command, e);
-            false
-        }
-    }
+    Ok(cmd)
 }
 
 /// Stop a running service. Uses exec-stop if defined, otherwise SIGTERM
@@ -143,44 +344,34 @@ pub fn stop_service(info: &ServiceInfo) {
     };
 
     crate::logger::write_log(&info.name, "Stopping service");
+    services::set_state(&info.name, ServiceState::Stopping, "stop requested");
 
     // Run exec-stop-pre hook if defined
     if let Some(ref hook) = info.config.exec_stop_pre {
-        run_hook(hook, &info.config);
+        run_hook("exec-stop-pre", hook, &info.config);
     }
 
     if let Some(ref exec_stop) = info.config.exec_stop {
         // Use the defined stop command
-        if !run_hook(exec_stop, &info.config) {
+        if !run_hook("exec-stop", exec_stop, &info.config) {
             // Stop command failed — fall back to SIGTERM
             eprintln!(
                 "rev: exec-stop failed for {}, falling back to SIGTERM",
                 info.name
             );
-            send_signal_with_timeout(pid, info.config.timeout_stop.unwrap_or(10));
+            send_signal_with_timeout(pid, info.config.stop_timeout());
         }
     } else {
         // No exec-stop — use SIGTERM + timeout
-        send_signal_with_timeout(pid, info.config.timeout_stop.unwrap_or(10));
+        send_signal_with_timeout(pid, info.config.stop_timeout());
     }
 }
 
 /// Send SIGTERM, wait up to `timeout_secs`, then SIGKILL if still alive.
 fn send_signal_with_timeout(pid: u32, timeout_secs: u64) {
-    unsafe {
-        libc::kill(pid as i32, libc::SIGTERM);
-    }
-
-    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout_secs);
-
-    // Poll whether the process is still alive
-    while std::time::Instant::now() < deadline {
-        // Check if process still exists (kill with signal 0)
-        let alive = unsafe { libc::kill(pid as i32, 0) } == 0;
-        if !alive {
-            return;
-        }
-        thread::sleep(std::time::Duration::from_millis(100));
+    supervisor::signal(pid, Signal::SIGTERM);
+    if supervisor::wait_exit(pid, std::time::Duration::from_secs(timeout_secs)) {
+        return;
     }
 
     // Timeout — force kill
@@ -188,9 +379,7 @@ fn send_signal_with_timeout(pid: u32, timeout_secs: u64) {
         "rev: service PID {} did not stop within {}s, sending SIGKILL",
         pid, timeout_secs
     );
-    unsafe {
-        libc::kill(pid as i32, libc::SIGKILL);
-    }
+    supervisor::signal(pid, Signal::SIGKILL);
 }
 
 /// Start a service from its .rsc config file path.
@@ -228,8 +417,10 @@ pub fn start_service_from_path(path: &std::path::Path) {
     );
 
     // Undo the registration if the service cannot actually be started, so a
-    // failed start does not leave a phantom registered-but-dead entry.
-    if !spawn_running(&config) {
+    // failed start does not leave a phantom registered-but-dead entry. One that
+    // only waited in vain for its bus names stays, Failed, so it can be listed,
+    // reloaded and started again.
+    if spawn_running(&config, None) == Spawn::Failed {
         services::deregister_service(&name);
     }
 }
@@ -240,6 +431,12 @@ pub fn start_service_from_path(path: &std::path::Path) {
 /// Unlike `start_service_from_path`, the service must already be registered (it
 /// is left registered on failure, since rev knows it independently of the bus).
 pub fn start_known_service(name: &str) -> bool {
+    start_registered(name, None)
+}
+
+/// [`start_known_service`], noting `restart_reason` in the new run's record
+/// when the restart policy is what starts it.
+fn start_registered(name: &str, restart_reason: Option<&str>) -> bool {
     let info = match services::get_service(name) {
         Some(i) => i,
         None => return false,
@@ -247,148 +444,10 @@ pub fn start_known_service(name: &str) -> bool {
     if info.is_running {
         return true;
     }
-    spawn_running(&info.config);
+    spawn_running(&info.config, restart_reason);
     services::get_service(name).map(|i| i.is_running).unwrap_or(false)
 }
 
-/// Start a user's `scope=user` services on their Lane, as the user.
-///
-/// Gathers OOTB/system-installed user-scope defaults from the system service
-/// dirs plus the account's vault-installed services, dependency-orders them, and
-/// forks each dropped to (uid, gid) with `WIREBUS_SOCKET` pointed at the lane.
-/// Each PID is recorded with the lane so logout tears it down. These run
-/// detached from the system service table (names are per-user, so they would
-/// collide), so they are reaped but not auto-restarted for now.
-pub fn start_user_services(
-    uid: u32,
-    gid: u32,
-    lane_socket: &std::path::Path,
-    account_uuid: &str,
-) {
-    let mut candidates: Vec<(String, ServiceConfig, std::path::PathBuf)> = Vec::new();
-    for dir in crate::parser::service_dirs() {
-        collect_user_scope(&dir, &mut candidates);
-    }
-    collect_user_scope(
-        &crate::bus::lanes::user_service_dir(account_uuid),
-        &mut candidates,
-    );
-    if candidates.is_empty() {
-        return;
-    }
-
-    let sortable: Vec<(String, ServiceConfig)> = candidates
-        .iter()
-        .map(|(name, config, _)| (name.clone(), config.clone()))
-        .collect();
-    let (order, _forced) = crate::init::ordering::start_order(&sortable);
-    for idx in order {
-        let (name, config, _) = &candidates[idx];
-        if let Some(pid) = spawn_lane_service(name, config, uid, gid, lane_socket) {
-            crate::bus::lanes::LANES.record_service(uid, pid);
-        }
-    }
-}
-
-/// Collect parseable `scope=user` services from `dir` into `out`.
-fn collect_user_scope(
-    dir: &std::path::Path,
-    out: &mut Vec<(String, ServiceConfig, std::path::PathBuf)>,
-) {
-    if !dir.exists() {
-        return;
-    }
-    for entry in walkdir::WalkDir::new(dir).into_iter().flatten() {
-        let path = entry.path();
-        if path.is_file()
-            && path.extension().and_then(|s| s.to_str()) == Some("rsc")
-            && let Some(config) = std::fs::read_to_string(path)
-                .ok()
-                .and_then(|t| crate::parser::deserialize_service_config(&t).ok())
-            && config.scope == crate::parser::ServiceScope::User
-        {
-            out.push((config.name.clone(), config, path.to_path_buf()));
-        }
-    }
-}
-
-/// Fork/exec one scope=user service as the lane's user. Returns the child PID,
-/// or None on failure. The service's identity is the logged-in user (the lane
-/// owner), never the config's `user=` field, so a user-scope service cannot ask
-/// to run as someone else.
-fn spawn_lane_service(
-    name: &str,
-    config: &ServiceConfig,
-    uid: u32,
-    gid: u32,
-    lane_socket: &std::path::Path,
-) -> Option<u32> {
-    let args = match shell_words::split(&config.exec_start) {
-        Ok(a) if !a.is_empty() => a,
-        _ => {
-            eprintln!("rev: user service {}: invalid exec-start", name);
-            return None;
-        }
-    };
-
-    match unsafe { nix::unistd::fork() } {
-        Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
-            println!("rev: started user service {} (PID {}, uid {})", name, child, uid);
-            Some(child.as_raw() as u32)
-        }
-        #[allow(unreachable_code)]
-        Ok(nix::unistd::ForkResult::Child) => {
-            unsafe {
-                let grp = [gid as libc::gid_t];
-                if libc::setgroups(1, grp.as_ptr()) != 0
-                    || libc::setgid(gid as libc::gid_t) != 0
-                    || libc::setuid(uid as libc::uid_t) != 0
-                {
-                    eprintln!("rev: user service {}: failed to drop to {}:{}", name, uid, gid);
-                    std::process::exit(1);
-                }
-                // Minimal environment: point the service at its lane bus, then
-                // layer the service's own env on top.
-                std::env::set_var("WIREBUS_SOCKET", lane_socket);
-                std::env::set_var("PATH", "/Core/Bin:/Construct/Bin");
-                for (key, value) in &config.env {
-                    std::env::set_var(key, value);
-                }
-            }
-            // Redirect output to a per-user log the user owns (best effort), so a
-            // user service does not write to rev's own console.
-            let log_dir = format!("/Transit/Ephemeral/user/{}/log", uid);
-            let _ = std::fs::create_dir_all(&log_dir);
-            if let Ok(f) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(format!("{}/{}.log", log_dir, name))
-            {
-                use std::os::unix::io::AsRawFd;
-                unsafe {
-                    libc::dup2(f.as_raw_fd(), libc::STDOUT_FILENO);
-                    libc::dup2(f.as_raw_fd(), libc::STDERR_FILENO);
-                }
-            }
-            if let Some(ref dir) = config.working_dir {
-                let _ = nix::unistd::chdir(dir.as_path());
-            }
-            use std::ffi::CString;
-            let cstr: Vec<CString> = args
-                .iter()
-                .map(|a| CString::new(a.clone()).expect("invalid argument"))
-                .collect();
-            let refs: Vec<&std::ffi::CStr> = cstr.iter().map(|s| s.as_c_str()).collect();
-            nix::unistd::execv(&cstr[0], &refs).expect("execv failed");
-            unreachable!()
-        }
-        Err(e) => {
-            eprintln!("rev: user service {}: fork failed: {}", name, e);
-            None
-        }
-    }
-}
-
 /// Resolve the (uid, gid) a service should run as from its `user`/`group`
 /// fields. `user` is a numeric uid or a UAC account name; `group` overrides the
 /// gid with a numeric value. Returns None when the user cannot be resolved, so
@@ -413,16 +472,61 @@ fn resolve_run_as(user: &str, group: Option<&str>) -> Option<(u32, u32)> {
     Some((uid, gid))
 }
 
+/// How a [`spawn_running`] attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Spawn {
+    /// The process was launched.
+    Launched,
+    /// Its `requires-bus-name` names did not appear in time. The service is
+    /// left Failed in the table, like any service that failed to start.
+    BusNamesMissing,
+    /// A pre-hook, its setup or the fork failed.
+    Failed,
+}
+
 /// Fork/exec a service's process from its config, running its start hooks and
 /// recording the child PID. Assumes the service is already registered. Returns
-/// whether the process was launched (false if a pre-hook or the fork failed).
-fn spawn_running(config: &ServiceConfig) -> bool {
+/// whether the process was launched, and if not, why. Each attempt opens a run
+/// in the service's history.
+fn spawn_running(config: &ServiceConfig, restart_reason: Option<&str>) -> Spawn {
     let name = config.name.clone();
 
+    // Claims the start: a service already starting or running cannot move to
+    // Starting again.
+    if !services::begin_run(&name, restart_reason) {
+        return Spawn::Failed;
+    }
+
+    // Peers it talks to over the bus come first, activated if need be.
+    if !config.requires_bus_name.is_empty() {
+        let timeout = std::time::Duration::from_secs(config.timeout_start.unwrap_or(90));
+        if let Err(e) = crate::bus::activation::require(&config.requires_bus_name, timeout) {
+            eprintln!("rev: service {}: {}", name, e);
+            crate::logger::write_log(&name, &format!("Not started: {}", e));
+            services::set_state(&name, ServiceState::Failed, "required bus names missing");
+            return Spawn::BusNamesMissing;
+        }
+    }
+
     // Resolve the user/group to run as before forking, so UAC is read from the
-    // parent. A service that names a user we cannot resolve is refused rather
-    // than run with rev's own (root) privileges.
+    // parent. A service that names a user we cannot resolve, or that finds no
+    // free dynamic uid, is refused rather than run with rev's own (root)
+    // privileges.
     let run_as = match config.user.as_deref() {
+        _ if config.dynamic_user => match dynamic_user::allocate(&name) {
+            Some(uid) => {
+                println!("rev: service {} runs as dynamic uid {}", name, uid);
+                Some((uid, uid))
+            }
+            None => {
+                eprintln!(
+                    "rev: service {}: no free dynamic uid, refusing to start",
+                    name
+                );
+                services::set_state(&name, ServiceState::Failed, "no free dynamic uid");
+                return Spawn::Failed;
+            }
+        },
         None => None,
         Some(u) => match resolve_run_as(u, config.group.as_deref()) {
             Some(ids) => Some(ids),
@@ -431,38 +535,94 @@ fn spawn_running(config: &ServiceConfig) -> bool {
                     "rev: service {}: cannot resolve user '{}', refusing to start as root",
                     name, u
                 );
-                return false;
+                services::set_state(&name, ServiceState::Failed, "cannot resolve user");
+                return Spawn::Failed;
             }
         },
     };
 
+    // Managed directories exist, owned by the service's user, before any of
+    // its commands runs.
+    if let Err(e) = directories::prepare(config, run_as) {
+        eprintln!("rev: service {}: cannot prepare directory {}", name, e);
+        services::set_state(&name, ServiceState::Failed, "cannot prepare directories");
+        dynamic_user::release(&name);
+        return Spawn::Failed;
+    }
+
     // Run exec-start-pre hook
     if let Some(ref hook) = config.exec_start_pre {
-        if !run_hook(hook, config) {
+        if !run_hook("exec-start-pre", hook, config) {
             eprintln!("rev: exec-start-pre failed for {}, aborting start", name);
-            return false;
+            services::set_state(&name, ServiceState::Failed, "exec-start-pre failed");
+            dynamic_user::release(&name);
+            return Spawn::Failed;
         }
     }
 
+    // Secrets last, so they exist only once nothing else can fail the start
+    // before fork.
+    let credentials = match credentials::prepare(config, run_as) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("rev: service {}: cannot install credentials: {}", name, e);
+            services::set_state(&name, ServiceState::Failed, "cannot install credentials");
+            dynamic_user::release(&name);
+            return Spawn::Failed;
+        }
+    };
+
     println!("rev: starting service {}", name);
+    let cgroup = cgroup::prepare(&name);
+    if let Some(ref cgroup) = cgroup
+        && let Err(e) = cgroup::apply_limits(cgroup, config)
+    {
+        eprintln!("rev: cannot set limits on {}: {}", cgroup.display(), e);
+    }
+    let oom_baseline = cgroup::oom_kills(&name);
+    // Opened before the fork: the child must not take rev's locks.
+    let log = crate::logger::open_log_fds(&name);
+    let default_env = crate::settings::get().env.clone();
 
-    match unsafe { nix::unistd::fork() } {
+    match supervisor::fork() {
         Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
             println!("rev: {} started (PID {})", name, child);
             crate::logger::write_log(&name, &format!("Service started (PID {})", child));
-            services::update_service_pid(Some(&name), Some(child.as_raw() as i32), None);
+            let pid = child.as_raw() as u32;
+            services::mark_service_started(&name, pid);
+            scheduler::service_started(&name, pid);
+            let watched = name.clone();
+            supervisor::watch(pid, move |exit| {
+                let oom_killed = memory::killed_by_oom(&watched, exit, oom_baseline);
+                handle_exit(pid, exit, oom_killed)
+            });
+            if let Some(ref cgroup) = cgroup {
+                memory::watch(&name, pid, cgroup.clone());
+            }
+            if config.health_check.is_some() {
+                health::watch(config, pid);
+            }
+            idle::watch(config, pid);
 
             // Run exec-start-post hook
             if let Some(ref hook) = config.exec_start_post {
-                run_hook(hook, config);
+                run_hook("exec-start-post", hook, config);
             }
-            true
+            Spawn::Launched
         }
         #[allow(unreachable_code)]
         Ok(nix::unistd::ForkResult::Child) => {
+            // Join the service's cgroup first, so everything it starts is
+            // accounted to it.
+            if let Some(ref cgroup) = cgroup
+                && let Err(e) = cgroup::enter(cgroup)
+            {
+                eprintln!("rev: failed to enter cgroup {}: {}", cgroup.display(), e);
+            }
+
             // Redirect stdout/stderr to log file
             use std::os::unix::io::AsRawFd;
-            match crate::logger::open_log_fds(&config.name) {
+            match log {
                 Ok((stdout_file, stderr_file)) => unsafe {
                     libc::dup2(stdout_file.as_raw_fd(), libc::STDOUT_FILENO);
                     libc::dup2(stderr_file.as_raw_fd(), libc::STDERR_FILENO);
@@ -472,12 +632,27 @@ fn spawn_running(config: &ServiceConfig) -> bool {
                 }
             }
 
-            // Set environment variables
+            // Set environment variables: rev.toml's defaults, then the service's
+            for (key, value) in &default_env {
+                unsafe {
+                    std::env::set_var(key, value);
+                }
+            }
             for (key, value) in &config.env {
                 unsafe {
                     std::env::set_var(key, value);
                 }
             }
+            for (key, path) in directories::env(config) {
+                unsafe {
+                    std::env::set_var(key, path);
+                }
+            }
+            if let Some(ref dir) = credentials {
+                unsafe {
+                    std::env::set_var(credentials::ENV_VAR, dir);
+                }
+            }
 
             // Change working directory if specified
             if let Some(ref dir) = config.working_dir {
@@ -534,37 +709,22 @@ fn spawn_running(config: &ServiceConfig) -> bool {
         }
         Err(e) => {
             eprintln!("rev: fork failed: {}", e);
-            false
+            services::set_state(&name, ServiceState::Failed, "fork failed");
+            dynamic_user::release(&name);
+            credentials::remove(&name);
+            Spawn::Failed
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{collect_user_scope, resolve_run_as};
-    use crate::parser::{ServiceConfig, ServiceScope};
-
-    #[test]
-    fn collect_user_scope_takes_only_user_services() {
-        let dir = tempfile::tempdir().unwrap();
-        let write = |name: &str, scope: ServiceScope| {
-            let cfg = ServiceConfig {
-                name: name.to_string(),
-                exec_start: "/bin/true".to_string(),
-                scope,
-                ..Default::default()
-            };
-            let toml = crate::parser::serialize_service_config(&cfg).unwrap();
-            std::fs::write(dir.path().join(format!("{name}.rsc")), toml).unwrap();
-        };
-        write("a-user", ServiceScope::User);
-        write("b-system", ServiceScope::System);
-
-        let mut out = Vec::new();
-        collect_user_scope(dir.path(), &mut out);
-        let names: Vec<&str> = out.iter().map(|(n, _, _)| n.as_str()).collect();
-        assert_eq!(names, ["a-user"]);
-    }
+    use super::{
+        resolve_run_as, supervisor::Exit, template_of, trigger_env, unresolved_trigger,
+        within_limit,
+    };
+    use crate::parser::ServiceConfig;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn numeric_user_resolves_without_uac() {
@@ -575,4 +735,62 @@ mod tests {
         // A non-numeric group is ignored (kept as the user's gid), not fatal.
         assert_eq!(resolve_run_as("1000", Some("staff")), Some((1000, 1000)));
     }
+
+    #[test]
+    fn trigger_targets_resolve_to_services_or_templates() {
+        assert_eq!(
+            template_of("notify/crash-reporter@web"),
+            Some("notify/crash-reporter@")
+        );
+        assert_eq!(template_of("notify/crash-reporter@"), None);
+        assert_eq!(template_of("notify/crash-reporter"), None);
+
+        let loaded = ["notify/crash-reporter@", "cleanup"];
+        let is_loaded = |name: &str| loaded.contains(&name);
+        let mut config = ServiceConfig {
+            name: "com.test.app/web".to_string(),
+            on_failure: vec!["notify/crash-reporter@%n".to_string()],
+            on_success: vec!["cleanup".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(unresolved_trigger(&config, is_loaded), None);
+        config.on_success.push("%n-archiver".to_string());
+        assert_eq!(
+            unresolved_trigger(&config, is_loaded).as_deref(),
+            Some("%n-archiver")
+        );
+    }
+
+    #[test]
+    fn triggered_services_learn_how_the_run_ended() {
+        let crash = Exit::Signal {
+            signal: libc::SIGSEGV,
+            core_dumped: true,
+        };
+        let env: std::collections::HashMap<_, _> =
+            trigger_env("on-failure", "com.test.app/worker", crash)
+                .into_iter()
+                .collect();
+        assert_eq!(env["REV_TRIGGER"], "on-failure");
+        assert_eq!(env["REV_TRIGGER_SERVICE"], "com.test.app/worker");
+        assert_eq!(
+            env["REV_TRIGGER_EXIT_STATUS"],
+            "killed by SIGSEGV (core dumped)"
+        );
+        assert_eq!(env["REV_TRIGGER_EXIT_SIGNAL"], "11");
+        assert!(!env.contains_key("REV_TRIGGER_EXIT_CODE"));
+    }
+    #[test]
+    fn the_start_limit_counts_restarts_in_a_window() {
+        let limit = (3, Duration::from_secs(10));
+        let start = Instant::now();
+        let mut times = Vec::new();
+        for i in 0..3 {
+            assert!(within_limit(&mut times, start + Duration::from_secs(i), limit));
+        }
+        // A crash loop is given up on once the burst is used up...
+        assert!(!within_limit(&mut times, start + Duration::from_secs(3), limit));
+        // ...but restarts spread over more than the window are not.
+        assert!(within_limit(&mut times, start + Duration::from_secs(11), limit));
+    }
 }
@@ -0,0 +1,327 @@
+//! Path-triggered services: start a service when a filesystem condition holds.
+//!
+//! The event-driven counterpart of the cron scheduler. A service lists paths in
+//! `on-path-changed`, `on-path-exists`, `on-directory-not-empty` or
+//! `on-path-modified`; rev watches them with inotify and starts the service
+//! (if it is not already running) when one fires. Such services are loaded but
+//! not started at boot, so a spooler or thumbnailer costs nothing until files
+//! land in its drop directory.
+//!
+//! Paths are watched through their parent directory, so a path that does not
+//! exist yet can still be waited on. `on-directory-not-empty` watches the
+//! directory itself. The two level conditions (`exists`, `not-empty`) are also
+//! checked when the watches are set up, and again after the service exits
+//! cleanly, so a drop directory that filled up while the service ran is drained
+//! by another run instead of waiting for the next file.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+
+use crate::parser::{ServiceConfig, ServiceInfo};
+
+/// Which filesystem condition a trigger waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathCondition {
+    Changed,
+    Exists,
+    DirectoryNotEmpty,
+    Modified,
+}
+
+impl PathCondition {
+    fn as_str(self) -> &'static str {
+        match self {
+            PathCondition::Changed => "on-path-changed",
+            PathCondition::Exists => "on-path-exists",
+            PathCondition::DirectoryNotEmpty => "on-directory-not-empty",
+            PathCondition::Modified => "on-path-modified",
+        }
+    }
+
+    /// Whether an event with `mask`, on the watched path itself, satisfies this
+    /// condition.
+    fn fires_on(self, mask: AddWatchFlags) -> bool {
+        let appeared = AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO;
+        let changed = appeared
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_ATTRIB;
+        match self {
+            PathCondition::Exists | PathCondition::DirectoryNotEmpty => mask.intersects(appeared),
+            PathCondition::Changed => mask.intersects(changed),
+            PathCondition::Modified => mask.intersects(changed | AddWatchFlags::IN_MODIFY),
+        }
+    }
+
+    /// Whether the condition holds right now. Only the level conditions can;
+    /// `changed`/`modified` are edges and never hold on their own.
+    fn holds(self, path: &Path) -> bool {
+        match self {
+            PathCondition::Exists => path.exists(),
+            PathCondition::DirectoryNotEmpty => std::fs::read_dir(path)
+                .map(|mut entries| entries.next().is_some())
+                .unwrap_or(false),
+            PathCondition::Changed | PathCondition::Modified => false,
+        }
+    }
+}
+
+/// One service waiting on one path.
+#[derive(Debug, Clone)]
+struct Trigger {
+    service: String,
+    condition: PathCondition,
+    path: PathBuf,
+}
+
+impl Trigger {
+    /// The directory inotify must watch for this trigger, and the entry name in
+    /// it that the trigger cares about (`None` = any entry).
+    fn watch_target(&self) -> Option<(PathBuf, Option<OsString>)> {
+        if self.condition == PathCondition::DirectoryNotEmpty {
+            return Some((self.path.clone(), None));
+        }
+        let parent = self.path.parent()?.to_path_buf();
+        let name = self.path.file_name()?.to_os_string();
+        Some((parent, Some(name)))
+    }
+}
+
+/// Every trigger a service config declares.
+fn triggers_for(config: &ServiceConfig) -> Vec<Trigger> {
+    let lists = [
+        (PathCondition::Changed, &config.on_path_changed),
+        (PathCondition::Exists, &config.on_path_exists),
+        (
+            PathCondition::DirectoryNotEmpty,
+            &config.on_directory_not_empty,
+        ),
+        (PathCondition::Modified, &config.on_path_modified),
+    ];
+    lists
+        .into_iter()
+        .flat_map(|(condition, paths)| {
+            paths.iter().map(move |path| Trigger {
+                service: config.name.clone(),
+                condition,
+                path: path.clone(),
+            })
+        })
+        .collect()
+}
+
+/// A trigger and the directory entry it waits on (`None` = any entry).
+type Watched = (Trigger, Option<OsString>);
+
+struct PathWatcher {
+    inotify: Inotify,
+    /// Watch descriptor -> the triggers waiting on events in that directory.
+    watches: Mutex<HashMap<WatchDescriptor, Vec<Watched>>>,
+}
+
+static WATCHER: OnceLock<PathWatcher> = OnceLock::new();
+
+fn watch_flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CREATE
+        | AddWatchFlags::IN_CLOSE_WRITE
+        | AddWatchFlags::IN_MODIFY
+        | AddWatchFlags::IN_ATTRIB
+        | AddWatchFlags::IN_DELETE
+        | AddWatchFlags::IN_MOVED_FROM
+        | AddWatchFlags::IN_MOVED_TO
+        | AddWatchFlags::IN_ONLYDIR
+}
+
+/// Start a triggered service if it is not already running.
+fn fire(trigger: &Trigger) {
+    let Some(info) = crate::init::services::get_service(&trigger.service) else {
+        return;
+    };
+    if info.is_running {
+        return;
+    }
+    println!(
+        "rev: {} triggered by {} {}",
+        trigger.service,
+        trigger.condition.as_str(),
+        trigger.path.display()
+    );
+    crate::logger::write_log(
+        &trigger.service,
+        &format!(
+            "Starting on path trigger ({} {})",
+            trigger.condition.as_str(),
+            trigger.path.display()
+        ),
+    );
+    super::start_known_service(&trigger.service);
+}
+
+/// Start the path watcher. Spawns a background thread that reads inotify
+/// events and starts services whose condition fired.
+pub fn start_path_watcher() {
+    let inotify = match Inotify::init(InitFlags::IN_CLOEXEC) {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!(
+                "rev: inotify unavailable ({}), path-triggered services disabled",
+                e
+            );
+            return;
+        }
+    };
+    let watcher = WATCHER.get_or_init(|| PathWatcher {
+        inotify,
+        watches: Mutex::new(HashMap::new()),
+    });
+    refresh();
+
+    thread::spawn(move || {
+        loop {
+            let events = match watcher.inotify.read_events() {
+                Ok(e) => e,
+                Err(nix::errno::Errno::EINTR) => continue,
+                Err(e) => {
+                    eprintln!("rev: path watcher: inotify read failed: {}", e);
+                    break;
+                }
+            };
+            for event in events {
+                let fired: Vec<Trigger> = {
+                    let watches = watcher.watches.lock().expect("path watch lock poisoned");
+                    let Some(triggers) = watches.get(&event.wd) else {
+                        continue;
+                    };
+                    triggers
+                        .iter()
+                        .filter(|(t, name)| {
+                            (name.is_none() || *name == event.name)
+                                && t.condition.fires_on(event.mask)
+                        })
+                        .map(|(t, _)| t.clone())
+                        .collect()
+                };
+                for trigger in &fired {
+                    fire(trigger);
+                }
+            }
+        }
+    });
+}
+
+/// Rebuild the watches from the service table. Called once the watcher starts
+/// and after every reload, so added, edited and removed triggers take effect.
+/// Level conditions that already hold fire immediately.
+pub fn refresh() {
+    let Some(watcher) = WATCHER.get() else { return };
+    let triggers: Vec<Trigger> = crate::init::services::list_services()
+        .iter()
+        .flat_map(|(_, info)| triggers_for(&info.config))
+        .collect();
+
+    {
+        let mut watches = watcher.watches.lock().expect("path watch lock poisoned");
+        for wd in watches.keys() {
+            let _ = watcher.inotify.rm_watch(*wd);
+        }
+        watches.clear();
+        for trigger in &triggers {
+            let Some((dir, name)) = trigger.watch_target() else {
+                eprintln!(
+                    "rev: {}: cannot watch {}, no parent directory",
+                    trigger.service,
+                    trigger.path.display()
+                );
+                continue;
+            };
+            match watcher.inotify.add_watch(&dir, watch_flags()) {
+                Ok(wd) => watches.entry(wd).or_default().push((trigger.clone(), name)),
+                Err(e) => eprintln!(
+                    "rev: {}: cannot watch {} for {}: {}",
+                    trigger.service,
+                    dir.display(),
+                    trigger.condition.as_str(),
+                    e
+                ),
+            }
+        }
+    }
+
+    for trigger in triggers.iter().filter(|t| t.condition.holds(&t.path)) {
+        fire(trigger);
+    }
+}
+
+/// Called when a service exits without being restarted. A clean exit while one
+/// of its level conditions still holds (the drop directory is not empty yet)
+/// starts it again; a failed run does not, so a broken service cannot spin.
+pub fn service_exited(info: &ServiceInfo, exit_code: Option<i32>) {
+    if exit_code != Some(0) || WATCHER.get().is_none() {
+        return;
+    }
+    if let Some(trigger) = triggers_for(&info.config)
+        .into_iter()
+        .find(|t| t.condition.holds(&t.path))
+    {
+        fire(&trigger);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers_cover_every_field() {
+        let config = ServiceConfig {
+            name: "spooler".to_string(),
+            on_path_changed: vec![PathBuf::from("/a/changed")],
+            on_path_exists: vec![PathBuf::from("/a/flag")],
+            on_directory_not_empty: vec![PathBuf::from("/a/drop")],
+            on_path_modified: vec![PathBuf::from("/a/log")],
+            ..Default::default()
+        };
+        assert!(config.is_path_triggered());
+        let t = triggers_for(&config);
+        assert_eq!(t.len(), 4);
+        // A file is watched through its parent, a drop directory directly.
+        assert_eq!(
+            t[1].watch_target(),
+            Some((PathBuf::from("/a"), Some("flag".into())))
+        );
+        assert_eq!(t[2].watch_target(), Some((PathBuf::from("/a/drop"), None)));
+    }
+
+    #[test]
+    fn edge_conditions_match_their_events() {
+        let close = AddWatchFlags::IN_CLOSE_WRITE;
+        let modify = AddWatchFlags::IN_MODIFY;
+        let create = AddWatchFlags::IN_CREATE;
+        assert!(PathCondition::Changed.fires_on(close));
+        assert!(!PathCondition::Changed.fires_on(modify));
+        assert!(PathCondition::Modified.fires_on(modify));
+        assert!(PathCondition::Exists.fires_on(create));
+        assert!(!PathCondition::Exists.fires_on(close));
+    }
+
+    #[test]
+    fn level_conditions_check_the_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        let drop = dir.path().join("drop");
+        std::fs::create_dir(&drop).unwrap();
+        assert!(!PathCondition::DirectoryNotEmpty.holds(&drop));
+        assert!(!PathCondition::Exists.holds(&drop.join("job")));
+
+        std::fs::write(drop.join("job"), b"x").unwrap();
+        assert!(PathCondition::DirectoryNotEmpty.holds(&drop));
+        assert!(PathCondition::Exists.holds(&drop.join("job")));
+        // Edge conditions never hold on their own.
+        assert!(!PathCondition::Changed.holds(&drop.join("job")));
+    }
+}
@@ -0,0 +1,153 @@
+//! Persisted history of timer-started runs.
+//!
+//! Every run the scheduler starts (cron or monotonic timer) is recorded when
+//! its process exits: when it started, how long it ran, and how it ended. A
+//! start that fails before a process runs is recorded as a failed run, and a
+//! firing skipped by `schedule-overlap = "skip"` is recorded too, so "did last
+//! night's backup run, and did it succeed?" has an answer after a reboot. The
+//! last [`HISTORY_LEN`] runs of each service are kept, one line per run, under
+//! `/Vault/State/rev/runs/`.
+
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Runs kept per service.
+pub const HISTORY_LEN: usize = 30;
+
+/// How a recorded run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunResult {
+    Exited(i32),
+    /// Ended by a signal (stopped, replaced, or crashed).
+    Killed,
+    /// The start failed before a process ran (a hook, bus names, fork).
+    StartFailed,
+    /// Not started: the previous run was still going.
+    Skipped,
+}
+
+/// One timer-started run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledRun {
+    pub started: DateTime<Utc>,
+    /// `None` for a skipped run.
+    pub duration: Option<Duration>,
+    pub result: RunResult,
+}
+
+impl ScheduledRun {
+    /// `success`, `failed (exit 3)`, `killed by signal`, `failed to start`,
+    /// or `skipped`.
+    pub fn describe(&self) -> String {
+        match self.result {
+            RunResult::Exited(0) => "success".to_string(),
+            RunResult::Exited(code) => format!("failed (exit {})", code),
+            RunResult::Killed => "killed by signal".to_string(),
+            RunResult::StartFailed => "failed to start".to_string(),
+            RunResult::Skipped => "skipped (previous run still active)".to_string(),
+        }
+    }
+
+    /// `<started>\t<seconds or ->\t<exit:N | killed | start-failed | skipped>`
+    fn encode(&self) -> String {
+        let duration = self
+            .duration
+            .map(|d| d.as_secs().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let result = match self.result {
+            RunResult::Exited(code) => format!("exit:{}", code),
+            RunResult::Killed => "killed".to_string(),
+            RunResult::StartFailed => "start-failed".to_string(),
+            RunResult::Skipped => "skipped".to_string(),
+        };
+        format!("{}\t{}\t{}", self.started.to_rfc3339(), duration, result)
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let started = DateTime::parse_from_rfc3339(fields.next()?)
+            .ok()?
+            .with_timezone(&Utc);
+        let duration = match fields.next()? {
+            "-" => None,
+            secs => Some(Duration::from_secs(secs.parse().ok()?)),
+        };
+        let result = match fields.next()? {
+            "killed" => RunResult::Killed,
+            "start-failed" => RunResult::StartFailed,
+            "skipped" => RunResult::Skipped,
+            other => RunResult::Exited(other.strip_prefix("exit:")?.parse().ok()?),
+        };
+        Some(ScheduledRun {
+            started,
+            duration,
+            result,
+        })
+    }
+}
+
+fn history_dir() -> PathBuf {
+    if cfg!(debug_assertions) {
+        PathBuf::from("./Vault/State/rev/runs")
+    } else {
+        PathBuf::from("/Vault/State/rev/runs")
+    }
+}
+
+fn history_file(name: &str) -> PathBuf {
+    history_dir().join(super::scheduler::file_name_for(name))
+}
+
+/// The recorded runs of `name`, oldest first. Unreadable lines are skipped.
+pub fn read(name: &str) -> Vec<ScheduledRun> {
+    std::fs::read_to_string(history_file(name))
+        .map(|text| text.lines().filter_map(ScheduledRun::decode).collect())
+        .unwrap_or_default()
+}
+
+/// Append a run to `name`'s history, dropping the oldest beyond
+/// [`HISTORY_LEN`].
+pub fn record(name: &str, run: ScheduledRun) {
+    let mut runs = read(name);
+    runs.push(run);
+    let keep = runs.len().saturating_sub(HISTORY_LEN);
+    let text: String = runs[keep..].iter().map(|r| r.encode() + "\n").collect();
+    if let Err(e) = std::fs::create_dir_all(history_dir())
+        .and_then(|_| std::fs::write(history_file(name), text))
+    {
+        eprintln!("rev: cannot record run history of {}: {}", name, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_round_trip_through_their_line_format() {
+        let started = DateTime::parse_from_rfc3339("2026-03-01T03:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        for result in [
+            RunResult::Exited(0),
+            RunResult::Exited(3),
+            RunResult::Killed,
+            RunResult::StartFailed,
+        ] {
+            let run = ScheduledRun {
+                started,
+                duration: Some(Duration::from_secs(754)),
+                result,
+            };
+            assert_eq!(ScheduledRun::decode(&run.encode()), Some(run));
+        }
+        let skipped = ScheduledRun {
+            started,
+            duration: None,
+            result: RunResult::Skipped,
+        };
+        assert_eq!(ScheduledRun::decode(&skipped.encode()), Some(skipped));
+        assert_eq!(ScheduledRun::decode("garbage"), None);
+    }
+}
@@ -1,95 +1,873 @@
-//! Cron scheduler for periodic service execution.
+//! Cron and monotonic timers for periodic service execution.
 //!
-//! Spawns a background tokio task that checks service schedules every minute.
-//! When a service's cron schedule matches the current time, it either starts
-//! the service (if not running) or restarts it (if force_restart_on_schedule).
+//! One background tokio task owns every timer. Each `schedule` is parsed once,
+//! when the scheduler first sees it (or it changes in a reload), and its next
+//! fire time is computed with croner and kept in a min-heap. The task sleeps
+//! until the earliest deadline of either kind, fires what is due, and goes back
+//! to sleep. A reload or a service stopping wakes it early, since either can
+//! move a deadline.
+//!
+//! When a cron schedule fires, the service is started (if not running) or
+//! restarted (if force_restart_on_schedule). Either runs as a queued job off
+//! the task, since a start or stop can block for a long time. A `persistent` schedule also fires
+//! for a run that was missed entirely (the machine was off or asleep at the
+//! time), using the last run time stored under [`stamp_dir`].
+//!
+//! The monotonic timers are `on-boot-sec`, `on-startup-sec` and
+//! `on-active-sec`, which fire once, and `on-unit-inactive-sec`, which fires
+//! each time the service has been stopped for that long.
+//! `randomized-delay-sec` pushes every firing back by a random amount.
+//!
+//! Cron times are wall-clock times but tokio sleeps on the monotonic clock, so
+//! the task never sleeps longer than [`MAX_SLEEP`] and compares the two clocks
+//! on each wake. A forward step (NTP sync, RTC fix, resume) fires each schedule
+//! it skipped once, rather than once per skipped occurrence. A backward step
+//! never re-runs an occurrence that already ran.
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use croner::Cron;
-use std::str::FromStr;
+use once_cell::sync::Lazy;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use super::jobs::{self, JobKind};
+use super::schedule_history::{self, RunResult, ScheduledRun};
+use crate::bus::protocol::{TimerEntry, TimerRun};
+use crate::parser::{CronStr, ScheduleOverlap, ServiceConfig, ServiceInfo};
+
+/// Longest the scheduler sleeps without looking at the wall clock.
+const MAX_SLEEP: Duration = Duration::from_secs(30);
+
+/// How far the wall clock may drift from the monotonic clock between two
+/// wakes before it counts as a step.
+const CLOCK_STEP: chrono::Duration = chrono::Duration::seconds(2);
+
+/// When the scheduler started; `on-startup-sec` counts from here.
+static STARTED: Lazy<Instant> = Lazy::new(Instant::now);
+
+static TIMERS: Lazy<Mutex<Timers>> = Lazy::new(|| Mutex::new(Timers::default()));
+
+/// Wakes the scheduler before its deadline.
+static WAKE: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Every timer the scheduler knows, keyed by service name.
+#[derive(Default)]
+struct Timers {
+    cron: HashMap<String, CronTimer>,
+    /// Next fire time of each cron timer. Entries are not removed when a timer
+    /// changes; a popped entry that no longer matches its timer's `next` is
+    /// stale and skipped.
+    heap: BinaryHeap<Reverse<(DateTime<Utc>, String)>>,
+    monotonic: HashMap<String, TimerState>,
+    /// Both clocks at the previous wake, to spot a wall-clock step.
+    last_wake: Option<(Instant, DateTime<Utc>)>,
+    /// Services with a run queued behind the current one
+    /// (`schedule-overlap = "queue"`).
+    queued: HashSet<String>,
+    /// Timer-started runs whose job has not forked a process yet, with the
+    /// time the job began.
+    pending: HashMap<String, DateTime<Utc>>,
+    /// Timer-started runs still going, by pid: service and start time,
+    /// recorded in the run history when that pid exits.
+    active: HashMap<u32, (String, DateTime<Utc>)>,
+}
+
+/// A service's parsed cron schedule.
+struct CronTimer {
+    expr: CronStr,
+    cron: Cron,
+    tz: Tz,
+    /// `None` when the expression has no future match.
+    next: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+}
 
-/// Start the cron scheduler background task.
+/// Start the scheduler background task.
 pub fn start_scheduler() {
-    tokio::spawn(async {
-        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    Lazy::force(&STARTED);
 
+    tokio::spawn(async {
         loop {
-            interval.tick().await;
-            check_schedules();
+            let sleep = tick().min(MAX_SLEEP);
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {}
+                _ = WAKE.notified() => {}
+            }
+        }
+    });
+}
+
+/// Re-examine the timers soon, e.g. after a reload changed the table.
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+/// Start a service because one of its timers fired. A run still going is
+/// handled per the service's `schedule-overlap`.
+fn fire(info: &ServiceInfo, reason: &str) {
+    if info.is_running {
+        match info.config.overlap_policy() {
+            ScheduleOverlap::Skip => {
+                println!("rev: {} still running, skipping {} run", info.name, reason);
+                crate::logger::write_log(
+                    &info.name,
+                    &format!("Skipped {} run: previous run still active", reason),
+                );
+                schedule_history::record(
+                    &info.name,
+                    ScheduledRun {
+                        started: Utc::now(),
+                        duration: None,
+                        result: RunResult::Skipped,
+                    },
+                );
+            }
+            ScheduleOverlap::Queue => {
+                crate::logger::write_log(
+                    &info.name,
+                    &format!("Queued {} run behind the current one", reason),
+                );
+                TIMERS
+                    .lock()
+                    .expect("timers lock poisoned")
+                    .queued
+                    .insert(info.name.clone());
+            }
+            ScheduleOverlap::Replace => {
+                println!(
+                    "rev: scheduled restart for {} (schedule-overlap = replace)",
+                    info.name
+                );
+                crate::logger::write_log(
+                    &info.name,
+                    &format!(
+                        "Scheduled restart on {} (schedule-overlap = replace)",
+                        reason
+                    ),
+                );
+                // A restart job stops the run, waits for it to exit, then
+                // starts the next one; the stop is a requested one, so the
+                // restart policy does not start it a second time.
+                let name = info.name.clone();
+                tokio::task::spawn_blocking(move || run_job(&name, JobKind::Restart));
+            }
+        }
+    } else {
+        // Service not running — start it on schedule
+        println!("rev: scheduled start for {} ({})", info.name, reason);
+        crate::logger::write_log(&info.name, &format!("Starting on {}", reason));
+        if info.config_path.is_some() {
+            let name = info.name.clone();
+            tokio::task::spawn_blocking(move || run_job(&name, JobKind::Start));
+        }
+    }
+}
+
+/// Start or restart `name` as a queued job, as a timer-started run that began
+/// now. Blocks until the job has run (a start may wait for its bus names), so
+/// it is called off the scheduler task, which must keep serving every other
+/// timer. The process the job forks takes the run over (see
+/// [`service_started`]); a job that forks none records a failed start.
+fn run_job(name: &str, kind: JobKind) {
+    let started = Utc::now();
+    TIMERS
+        .lock()
+        .expect("timers lock poisoned")
+        .pending
+        .insert(name.to_string(), started);
+    jobs::enqueue(name, kind);
+    jobs::wait_idle(name);
+    let unstarted = TIMERS
+        .lock()
+        .expect("timers lock poisoned")
+        .pending
+        .remove(name);
+    // Still pending: no process was forked. A service found running was
+    // started by someone else, and this run is not its.
+    let running = crate::init::services::get_service(name).is_some_and(|i| i.is_running);
+    if unstarted.is_some() && !running {
+        schedule_history::record(
+            name,
+            ScheduledRun {
+                started,
+                duration: (Utc::now() - started).to_std().ok(),
+                result: RunResult::StartFailed,
+            },
+        );
+    }
+}
+
+/// Called when a service's process is forked, before its exit is watched. A
+/// timer-started run waiting for it becomes that process's run, so its end
+/// lands in the run history.
+pub fn service_started(name: &str, pid: u32) {
+    let mut timers = TIMERS.lock().expect("timers lock poisoned");
+    if let Some(started) = timers.pending.remove(name) {
+        timers.active.insert(pid, (name.to_string(), started));
+    }
+}
+
+/// Fire after the service's randomized delay, if it has one.
+fn fire_delayed(info: ServiceInfo, reason: &'static str, delay: Duration) {
+    if delay.is_zero() {
+        fire(&info, reason);
+        return;
+    }
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        // Re-read: the service may have started or gone meanwhile.
+        if let Some(info) = crate::init::services::get_service(&info.name) {
+            fire(&info, reason);
         }
     });
 }
 
-fn check_schedules() {
+/// Sync the timers with the service table, fire whatever is due, and return
+/// how long until the next deadline.
+fn tick() -> Duration {
     let services = crate::init::services::list_services();
     let now = Utc::now();
+    let instant = Instant::now();
+    let since_boot = uptime().unwrap_or_else(|| instant.saturating_duration_since(*STARTED));
 
-    for (_name, info) in services {
-        let schedule = match &info.config.schedule {
-            Some(cron_str) => cron_str,
-            None => continue,
-        };
+    let mut to_fire: Vec<(ServiceInfo, &'static str, Duration)> = Vec::new();
+    let sleep = {
+        let mut timers = TIMERS.lock().expect("timers lock poisoned");
 
-        let cron = match Cron::from_str(&schedule.0) {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!(
-                    "rev: invalid cron schedule for {}: {}",
-                    info.name, e
+        if let Some((prev_instant, prev_wall)) = timers.last_wake {
+            let expected =
+                prev_wall + chrono::Duration::from_std(instant - prev_instant).unwrap_or_default();
+            let step = now - expected;
+            if step.abs() > CLOCK_STEP {
+                println!(
+                    "rev: wall clock stepped by {}s, rescheduling timers",
+                    step.num_seconds()
                 );
+                timers.reschedule(now);
+            }
+        }
+        timers.last_wake = Some((instant, now));
+
+        timers.sync_cron(&services, now);
+        for (name, at) in timers.pop_due(now) {
+            let Some((_, info)) = services.iter().find(|(n, _)| *n == name) else {
                 continue;
+            };
+            if info.config.persistent {
+                write_stamp(&name, now);
             }
-        };
+            // Far enough behind that the run was not merely a late wake.
+            let reason = if now - at > chrono::Duration::seconds(60) {
+                "missed cron schedule"
+            } else {
+                "cron schedule"
+            };
+            to_fire.push((
+                info.clone(),
+                reason,
+                random_delay(info.config.randomized_delay_sec),
+            ));
+        }
 
-        // Check if the cron expression matches the current minute.
-        // We check if there's a scheduled time between (now - 60s) and now.
-        let window_start = now - chrono::Duration::seconds(60);
-        let has_match = cron
-            .iter_from(window_start, croner::Direction::Forward)
-            .take(1)
-            .any(|t| t <= now);
+        timers.sync_monotonic(&services, instant);
+        let mut next_monotonic: Option<Duration> = None;
+        for (name, info) in &services {
+            let Some(state) = timers.monotonic.get_mut(name) else {
+                continue;
+            };
+            let Some((timer, remaining)) = pending(&info.config, state, since_boot, instant) else {
+                continue;
+            };
+            if !remaining.is_zero() {
+                next_monotonic = Some(next_monotonic.map_or(remaining, |n| n.min(remaining)));
+                continue;
+            }
+            match timer {
+                Monotonic::UnitInactive => state.inactive_since = None,
+                one_shot => state.fired.push(one_shot),
+            }
+            state.last = Some(now);
+            state.jitter = random_delay(info.config.randomized_delay_sec);
+            to_fire.push((info.clone(), timer.as_str(), Duration::ZERO));
+            // Another of its timers may be due too; look again right away.
+            next_monotonic = Some(Duration::ZERO);
+        }
 
-        if !has_match {
-            continue;
+        // Queued runs start once the run ahead of them has exited.
+        let queued: Vec<String> = timers.queued.iter().cloned().collect();
+        for name in queued {
+            match services.iter().find(|(n, _)| *n == name) {
+                Some((_, info)) if info.is_running => {}
+                Some((_, info)) => {
+                    timers.queued.remove(&name);
+                    to_fire.push((info.clone(), "queued timer", Duration::ZERO));
+                }
+                None => {
+                    timers.queued.remove(&name);
+                }
+            }
         }
 
-        if info.is_running {
-            if info.config.force_restart_on_schedule {
-                println!(
-                    "rev: scheduled restart for {} (force_restart_on_schedule)",
-                    info.name
-                );
-                crate::logger::write_log(
-                    &info.name,
-                    "Scheduled restart (force-restart-on-schedule = true)",
-                );
-                // Stop then restart
-                crate::service::stop_service(&info);
-                // The restart will happen via the restart policy in handle_exit,
-                // or we can restart directly after a brief delay
-                if let Some(ref config_path) = info.config_path {
-                    let path = std::path::PathBuf::from(config_path);
-                    let name = info.name.clone();
-                    std::thread::spawn(move || {
-                        // Wait for process to actually die
-                        std::thread::sleep(std::time::Duration::from_secs(2));
-                        crate::init::services::deregister_service(&name);
-                        crate::service::start_service_from_path(&path);
-                    });
+        let next_cron = timers
+            .heap
+            .peek()
+            .map(|Reverse((at, _))| (*at - now).to_std().unwrap_or(Duration::ZERO));
+        [next_cron, next_monotonic]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(MAX_SLEEP)
+    };
+
+    for (info, reason, delay) in to_fire {
+        fire_delayed(info, reason, delay);
+    }
+    sleep
+}
+
+/// The first occurrence of `cron`, read as local time in `tz`, strictly after
+/// `t`. croner walks local time, so a time skipped by a DST change fires at the
+/// next time that exists, and a repeated hour fires once.
+fn next_after(cron: &Cron, tz: Tz, t: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    cron.find_next_occurrence(&t.with_timezone(&tz), false)
+        .ok()
+        .map(|next| next.with_timezone(&Utc))
+}
+
+impl Timers {
+    /// Add timers for new or changed schedules and drop those whose service or
+    /// schedule is gone. Expressions are parsed only here.
+    fn sync_cron(&mut self, services: &[(String, ServiceInfo)], now: DateTime<Utc>) {
+        self.cron.retain(|name, timer| {
+            services.iter().any(|(n, i)| {
+                n == name
+                    && i.config.schedule.as_ref() == Some(&timer.expr)
+                    && i.config.schedule_timezone.unwrap_or(Tz::UTC) == timer.tz
+            })
+        });
+        for (name, info) in services {
+            let Some(expr) = &info.config.schedule else {
+                continue;
+            };
+            if self.cron.contains_key(name) {
+                continue;
+            }
+            let cron = expr.cron();
+            let tz = info.config.schedule_timezone.unwrap_or(Tz::UTC);
+            let (next, last) = if info.config.persistent {
+                // Count from the recorded run, however long ago, so a run that
+                // fell while rev was not running is due now. A service with no
+                // record yet starts counting now.
+                match read_stamp(name) {
+                    Some(last) => (next_after(&cron, tz, last), Some(last)),
+                    None => {
+                        write_stamp(name, now);
+                        (next_after(&cron, tz, now), None)
+                    }
                 }
+            } else {
+                (next_after(&cron, tz, now), None)
+            };
+            if let Some(at) = next {
+                self.heap.push(Reverse((at, name.clone())));
             }
-            // If not force_restart, leave it running
-        } else {
-            // Service not running — start it on schedule
-            println!("rev: scheduled start for {}", info.name);
-            crate::logger::write_log(&info.name, "Starting on cron schedule");
-            if let Some(ref config_path) = info.config_path {
-                let path = std::path::PathBuf::from(config_path);
-                let name = info.name.clone();
-                // Deregister first since it's already registered but not running
-                crate::init::services::deregister_service(&name);
-                crate::service::start_service_from_path(&path);
+            self.cron.insert(
+                name.clone(),
+                CronTimer {
+                    expr: expr.clone(),
+                    cron,
+                    tz,
+                    next,
+                    last,
+                },
+            );
+        }
+    }
+
+    /// Pop every cron timer due by `now`, recording the run and queueing its
+    /// next occurrence. Returns the names and the times they were due.
+    fn pop_due(&mut self, now: DateTime<Utc>) -> Vec<(String, DateTime<Utc>)> {
+        let mut due = Vec::new();
+        while let Some(Reverse((at, _))) = self.heap.peek()
+            && *at <= now
+        {
+            let Some(Reverse((at, name))) = self.heap.pop() else {
+                break;
+            };
+            let Some(timer) = self.cron.get_mut(&name) else {
+                continue;
+            };
+            if timer.next != Some(at) {
+                continue;
+            }
+            // Count from now, not from `at`: after a long sleep or a forward
+            // clock step every skipped occurrence collapses into this one run.
+            timer.last = Some(now);
+            timer.next = next_after(&timer.cron, timer.tz, now);
+            if let Some(next) = timer.next {
+                self.heap.push(Reverse((next, name.clone())));
+            }
+            due.push((name, at));
+        }
+        due
+    }
+
+    /// Recompute the pending cron times after a wall-clock step. A time already
+    /// passed is left to fire; a future one is counted again from now, but never
+    /// from before the last run, so a backward step cannot repeat it.
+    fn reschedule(&mut self, now: DateTime<Utc>) {
+        for (name, timer) in self.cron.iter_mut() {
+            if timer.next.is_none_or(|next| next <= now) {
+                continue;
+            }
+            let from = timer.last.map_or(now, |last| last.max(now));
+            timer.next = next_after(&timer.cron, timer.tz, from);
+            if let Some(next) = timer.next {
+                self.heap.push(Reverse((next, name.clone())));
+            }
+        }
+    }
+
+    fn sync_monotonic(&mut self, services: &[(String, ServiceInfo)], now: Instant) {
+        // Forget services that were removed or lost their timers in a reload.
+        self.monotonic.retain(|name, _| {
+            services
+                .iter()
+                .any(|(n, i)| n == name && has_monotonic_timer(&i.config))
+        });
+        for (name, info) in services {
+            if !has_monotonic_timer(&info.config) {
+                continue;
+            }
+            let state = self
+                .monotonic
+                .entry(name.clone())
+                .or_insert_with(|| TimerState::new(&info.config, now));
+            if info.is_running {
+                state.inactive_since = None;
             }
         }
     }
 }
+
+/// The next and last run of every timer, for `ListTimers`.
+pub fn list_timers() -> Vec<TimerEntry> {
+    let services = crate::init::services::list_services();
+    let now = Utc::now();
+    let instant = Instant::now();
+    let since_boot = uptime().unwrap_or_else(|| instant.saturating_duration_since(*STARTED));
+    let timers = TIMERS.lock().expect("timers lock poisoned");
+
+    let mut out = Vec::new();
+    for (name, info) in &services {
+        // The history survives reboots; the in-memory last fire time does not.
+        let recorded = schedule_history::read(name).pop();
+        let recorded_start = recorded.as_ref().map(|r| r.started.timestamp());
+        let last_result = if timers.active.values().any(|(n, _)| n == name) {
+            Some("running".to_string())
+        } else {
+            recorded.map(|r| r.describe())
+        };
+        if let Some(timer) = timers.cron.get(name) {
+            out.push(TimerEntry {
+                service: name.clone(),
+                timer: timer.expr.to_string(),
+                next_run: timer.next.map(|t| t.timestamp()),
+                last_run: timer.last.map(|t| t.timestamp()).or(recorded_start),
+                last_result: last_result.clone(),
+            });
+        }
+        if let Some(state) = timers.monotonic.get(name) {
+            let next =
+                pending(&info.config, state, since_boot, instant).and_then(|(_, remaining)| {
+                    chrono::Duration::from_std(remaining)
+                        .ok()
+                        .map(|d| (now + d).timestamp())
+                });
+            out.push(TimerEntry {
+                service: name.clone(),
+                timer: describe_monotonic(&info.config),
+                next_run: next,
+                last_run: state.last.map(|t| t.timestamp()).or(recorded_start),
+                last_result: last_result.clone(),
+            });
+        }
+    }
+    out
+}
+
+/// The recorded timer-started runs of `name`, newest first, for `TimerHistory`.
+pub fn history(name: &str) -> Vec<TimerRun> {
+    schedule_history::read(name)
+        .into_iter()
+        .rev()
+        .map(|run| TimerRun {
+            started: run.started.timestamp(),
+            duration_secs: run.duration.map(|d| d.as_secs()),
+            result: run.describe(),
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Persistent schedule stamps
+// ---------------------------------------------------------------------------
+
+/// Where the last run of each `persistent` schedule is recorded.
+fn stamp_dir() -> PathBuf {
+    if cfg!(debug_assertions) {
+        PathBuf::from("./Vault/State/rev/timers")
+    } else {
+        PathBuf::from("/Vault/State/rev/timers")
+    }
+}
+
+/// Service names nest (`com.vendor.app/backup`), so `/` is escaped to keep one
+/// flat file per service.
+pub(super) fn file_name_for(name: &str) -> String {
+    name.replace('%', "%25").replace('/', "%2F")
+}
+
+fn stamp_file(name: &str) -> PathBuf {
+    stamp_dir().join(file_name_for(name))
+}
+
+fn read_stamp(name: &str) -> Option<DateTime<Utc>> {
+    let text = std::fs::read_to_string(stamp_file(name)).ok()?;
+    DateTime::parse_from_rfc3339(text.trim())
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+fn write_stamp(name: &str, at: DateTime<Utc>) {
+    let path = stamp_file(name);
+    if let Err(e) =
+        std::fs::create_dir_all(stamp_dir()).and_then(|_| std::fs::write(&path, at.to_rfc3339()))
+    {
+        eprintln!("rev: cannot record last run of {}: {}", name, e);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Monotonic timers
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Monotonic {
+    Boot,
+    Startup,
+    Active,
+    UnitInactive,
+}
+
+impl Monotonic {
+    fn as_str(self) -> &'static str {
+        match self {
+            Monotonic::Boot => "on-boot-sec timer",
+            Monotonic::Startup => "on-startup-sec timer",
+            Monotonic::Active => "on-active-sec timer",
+            Monotonic::UnitInactive => "on-unit-inactive-sec timer",
+        }
+    }
+}
+
+/// The monotonic timer state of one service.
+#[derive(Debug)]
+struct TimerState {
+    /// When the timers were loaded; `on-active-sec` counts from here.
+    activated: Instant,
+    /// One-shot timers that have already fired.
+    fired: Vec<Monotonic>,
+    /// When the service was last seen to stop. `None` while it runs, and
+    /// before it has ever run under rev.
+    inactive_since: Option<Instant>,
+    /// The random delay added to the next firing.
+    jitter: Duration,
+    /// When one of these timers last fired.
+    last: Option<DateTime<Utc>>,
+}
+
+impl TimerState {
+    fn new(config: &ServiceConfig, now: Instant) -> Self {
+        TimerState {
+            activated: now,
+            fired: Vec::new(),
+            inactive_since: None,
+            jitter: random_delay(config.randomized_delay_sec),
+            last: None,
+        }
+    }
+}
+
+/// The earliest timer of `config` still to fire, and how long until it does
+/// (zero when it is due).
+fn pending(
+    config: &ServiceConfig,
+    state: &TimerState,
+    since_boot: Duration,
+    now: Instant,
+) -> Option<(Monotonic, Duration)> {
+    let secs = |s: Option<u64>| s.map(|s| Duration::from_secs(s) + state.jitter);
+    let since_startup = now.saturating_duration_since(*STARTED);
+    let since_active = now.saturating_duration_since(state.activated);
+    let since_inactive = state
+        .inactive_since
+        .map(|since| now.saturating_duration_since(since));
+    let candidates = [
+        (Monotonic::Boot, secs(config.on_boot_sec), Some(since_boot)),
+        (
+            Monotonic::Startup,
+            secs(config.on_startup_sec),
+            Some(since_startup),
+        ),
+        (
+            Monotonic::Active,
+            secs(config.on_active_sec),
+            Some(since_active),
+        ),
+        (
+            Monotonic::UnitInactive,
+            secs(config.on_unit_inactive_sec),
+            since_inactive,
+        ),
+    ];
+    candidates
+        .into_iter()
+        .filter(|(timer, _, _)| !state.fired.contains(timer))
+        .filter_map(|(timer, after, elapsed)| Some((timer, after?.saturating_sub(elapsed?))))
+        .min_by_key(|(_, remaining)| *remaining)
+}
+
+/// `on-boot-sec=30 on-unit-inactive-sec=600`, for listings.
+fn describe_monotonic(config: &ServiceConfig) -> String {
+    [
+        ("on-boot-sec", config.on_boot_sec),
+        ("on-startup-sec", config.on_startup_sec),
+        ("on-active-sec", config.on_active_sec),
+        ("on-unit-inactive-sec", config.on_unit_inactive_sec),
+    ]
+    .into_iter()
+    .filter_map(|(key, secs)| secs.map(|s| format!("{}={}", key, s)))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Called when a service's process is reaped. Completes the run-history entry
+/// of a timer-started run, starts counting `on-unit-inactive-sec`, and lets a
+/// queued run go.
+pub fn service_stopped(name: &str, pid: u32, exit_code: Option<i32>) {
+    let mut timers = TIMERS.lock().expect("timers lock poisoned");
+    if let Some((_, started)) = timers.active.remove(&pid) {
+        schedule_history::record(
+            name,
+            ScheduledRun {
+                started,
+                duration: (Utc::now() - started).to_std().ok(),
+                result: exit_code.map_or(RunResult::Killed, RunResult::Exited),
+            },
+        );
+    }
+    if let Some(state) = timers.monotonic.get_mut(name) {
+        state.inactive_since = Some(Instant::now());
+        WAKE.notify_one();
+    }
+    if timers.queued.contains(name) {
+        WAKE.notify_one();
+    }
+}
+
+fn has_monotonic_timer(config: &ServiceConfig) -> bool {
+    config.on_boot_sec.is_some()
+        || config.on_startup_sec.is_some()
+        || config.on_active_sec.is_some()
+        || config.on_unit_inactive_sec.is_some()
+}
+
+/// Time since the kernel booted (CLOCK_BOOTTIME, so it includes suspend).
+fn uptime() -> Option<Duration> {
+    let text = std::fs::read_to_string("/proc/uptime").ok()?;
+    let secs: f64 = text.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_secs_f64(secs))
+}
+
+/// A random delay of up to `max_sec` seconds (millisecond steps).
+fn random_delay(max_sec: Option<u64>) -> Duration {
+    let Some(max) = max_sec.filter(|m| *m > 0) else {
+        return Duration::ZERO;
+    };
+    let mut buf = [0u8; 8];
+    let n = unsafe { libc::getrandom(buf.as_mut_ptr().cast(), buf.len(), 0) };
+    let r = if n == buf.len() as isize {
+        u64::from_ne_bytes(buf)
+    } else {
+        // No entropy yet this early in boot: any spread beats none.
+        Utc::now().timestamp_subsec_nanos() as u64
+    };
+    Duration::from_millis(r % (max * 1000 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn config() -> ServiceConfig {
+        ServiceConfig {
+            name: "job".to_string(),
+            on_boot_sec: Some(30),
+            on_unit_inactive_sec: Some(600),
+            ..Default::default()
+        }
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn scheduled(expr: &str) -> (String, ServiceInfo) {
+        let config = ServiceConfig {
+            name: "job".to_string(),
+            schedule: Some(CronStr::from_str(expr).unwrap()),
+            ..Default::default()
+        };
+        (
+            "job".to_string(),
+            ServiceInfo {
+                name: "job".to_string(),
+                config,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn one_shot_timers_fire_once() {
+        let now = Instant::now();
+        let c = config();
+        let mut state = TimerState::new(&c, now);
+        assert_eq!(
+            pending(&c, &state, Duration::from_secs(10), now),
+            Some((Monotonic::Boot, Duration::from_secs(20)))
+        );
+        assert_eq!(
+            pending(&c, &state, Duration::from_secs(30), now),
+            Some((Monotonic::Boot, Duration::ZERO))
+        );
+        state.fired.push(Monotonic::Boot);
+        assert_eq!(pending(&c, &state, Duration::from_secs(90), now), None);
+    }
+
+    #[test]
+    fn unit_inactive_counts_from_the_last_stop() {
+        let c = config();
+        let start = Instant::now();
+        let mut state = TimerState::new(&c, start);
+        state.fired.push(Monotonic::Boot);
+        let later = start + Duration::from_secs(700);
+        // Never stopped under rev: nothing to count from.
+        assert_eq!(pending(&c, &state, Duration::from_secs(700), later), None);
+        state.inactive_since = Some(start + Duration::from_secs(200));
+        assert_eq!(
+            pending(&c, &state, Duration::from_secs(700), later),
+            Some((Monotonic::UnitInactive, Duration::from_secs(100)))
+        );
+        state.inactive_since = Some(start);
+        assert_eq!(
+            pending(&c, &state, Duration::from_secs(700), later),
+            Some((Monotonic::UnitInactive, Duration::ZERO))
+        );
+    }
+
+    #[test]
+    fn cron_fires_once_per_occurrence() {
+        let services = vec![scheduled("*/15 * * * * *")];
+        let mut timers = Timers::default();
+        timers.sync_cron(&services, at("2026-03-01T10:00:01Z"));
+        assert_eq!(timers.cron["job"].next, Some(at("2026-03-01T10:00:15Z")));
+
+        assert!(timers.pop_due(at("2026-03-01T10:00:14Z")).is_empty());
+        assert_eq!(timers.pop_due(at("2026-03-01T10:00:15Z")).len(), 1);
+        // The same instant again (a spurious wake) must not fire twice.
+        assert!(timers.pop_due(at("2026-03-01T10:00:15Z")).is_empty());
+        assert_eq!(timers.cron["job"].next, Some(at("2026-03-01T10:00:30Z")));
+    }
+
+    #[test]
+    fn clock_steps_neither_burst_nor_repeat() {
+        let services = vec![scheduled("0 * * * *")];
+        let mut timers = Timers::default();
+        timers.sync_cron(&services, at("2026-03-01T10:30:00Z"));
+
+        // Forward five hours: one run, not five.
+        assert_eq!(timers.pop_due(at("2026-03-01T15:30:00Z")).len(), 1);
+        assert_eq!(timers.cron["job"].next, Some(at("2026-03-01T16:00:00Z")));
+
+        // Back two hours: 14:00 and 15:00 already ran in effect; wait for 16:00.
+        timers.reschedule(at("2026-03-01T13:30:00Z"));
+        assert_eq!(timers.cron["job"].next, Some(at("2026-03-01T16:00:00Z")));
+        assert!(timers.pop_due(at("2026-03-01T14:00:00Z")).is_empty());
+    }
+
+    #[test]
+    fn edited_schedule_is_reparsed() {
+        let mut timers = Timers::default();
+        timers.sync_cron(&[scheduled("0 3 * * *")], at("2026-03-01T10:00:00Z"));
+        timers.sync_cron(&[scheduled("0 12 * * *")], at("2026-03-01T10:00:00Z"));
+        assert_eq!(timers.cron["job"].next, Some(at("2026-03-01T12:00:00Z")));
+        // The stale 03:00 heap entry is skipped.
+        let due = timers.pop_due(at("2026-03-02T04:00:00Z"));
+        assert_eq!(due, [("job".to_string(), at("2026-03-01T12:00:00Z"))]);
+    }
+
+    #[test]
+    fn persistent_schedule_catches_up_on_missed_runs() {
+        let cron = CronStr::from_str("0 3 * * *").unwrap().cron();
+        let last = at("2026-03-01T03:00:00Z");
+        assert_eq!(
+            next_after(&cron, Tz::UTC, last),
+            Some(at("2026-03-02T03:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn schedules_follow_their_time_zone_across_dst() {
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        let daily = CronStr::from_str("0 3 * * *").unwrap().cron();
+        // 03:00 Berlin is 02:00 UTC in winter and 01:00 UTC in summer.
+        assert_eq!(
+            next_after(&daily, berlin, at("2026-03-01T12:00:00Z")),
+            Some(at("2026-03-02T02:00:00Z"))
+        );
+        assert_eq!(
+            next_after(&daily, berlin, at("2026-07-01T12:00:00Z")),
+            Some(at("2026-07-02T01:00:00Z"))
+        );
+
+        // 02:30 does not exist on 29 March; it must not be skipped entirely.
+        let skipped_hour = CronStr::from_str("30 2 * * *").unwrap().cron();
+        let next = next_after(&skipped_hour, berlin, at("2026-03-28T12:00:00Z")).unwrap();
+        assert!(next < at("2026-03-29T12:00:00Z"));
+
+        // 02:30 happens twice on 25 October; it fires once that day.
+        let first = next_after(&skipped_hour, berlin, at("2026-10-24T12:00:00Z")).unwrap();
+        let second = next_after(&skipped_hour, berlin, first).unwrap();
+        assert!(second > at("2026-10-26T00:00:00Z"));
+    }
+
+    #[test]
+    fn randomized_delay_stays_in_range() {
+        assert_eq!(random_delay(None), Duration::ZERO);
+        for _ in 0..50 {
+            assert!(random_delay(Some(2)) <= Duration::from_secs(2));
+        }
+        assert_eq!(
+            stamp_file("com.app/backup").file_name().unwrap(),
+            "com.app%2Fbackup"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! pidfd-based supervision of rev's children.
+//!
+//! Every process rev starts (services, lane services, sessions, ExecAs
+//! children, the console login, hooks) is forked through [`fork`], which opens a
+//! pidfd for the child before anything else can reap it. Signals go through
+//! [`signal`] (`pidfd_send_signal`) and exits are waited for on the pidfd, so a
+//! PID that was reaped and recycled can never be signalled by mistake: once a
+//! child is reaped its entry is gone and [`signal`] does nothing.
+//!
+//! Exit notification comes from the pidfd becoming readable in the tokio
+//! reactor: [`watch`] spawns a task that waits for it, reaps exactly that child
+//! with `waitid(P_PIDFD)`, and runs the caller's exit handler. As PID 1, rev
+//! also inherits orphans it never started; a SIGCHLD-driven reaper collects
+//! those, peeking with `WNOWAIT` so it never reaps a supervised child.
+
+use std::collections::HashMap;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use nix::sys::signal::Signal;
+use nix::sys::wait::{Id, WaitPidFlag, WaitStatus, waitid, waitpid};
+use nix::unistd::ForkResult;
+use once_cell::sync::Lazy;
+use signal_hook::consts::signal::SIGCHLD;
+use signal_hook::iterator::Signals;
+use tokio::io::unix::AsyncFd;
+
+/// Supervised children: PID -> pidfd. An entry exists from the fork until the
+/// child is reaped; the orphan reaper leaves every PID in here alone. Held
+/// across `fork()` so a child that exits at once is not reaped as an orphan
+/// before it is recorded.
+static CHILDREN: Lazy<Mutex<HashMap<u32, Arc<OwnedFd>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The runtime exit watchers run on, captured by [`start`] so children forked
+/// from plain threads can be watched too.
+static RUNTIME: OnceLock<tokio::runtime::Handle> = OnceLock::new();
+
+/// Whether rev reaps orphans (set by [`start`]). Off when rev is not init, so a
+/// CLI or test process never reaps children it does not know about.
+static REAP_ORPHANS: AtomicBool = AtomicBool::new(false);
+
+fn pidfd_open(pid: u32) -> std::io::Result<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+fn pidfd(pid: u32) -> Option<Arc<OwnedFd>> {
+    CHILDREN
+        .lock()
+        .expect("children lock poisoned")
+        .get(&pid)
+        .cloned()
+}
+
+/// Start supervision: remember the tokio runtime for exit watchers and spawn
+/// the orphan reaper. Must be called from within the runtime, before the first
+/// child is forked.
+pub fn start() {
+    let _ = RUNTIME.set(tokio::runtime::Handle::current());
+    REAP_ORPHANS.store(true, Ordering::Relaxed);
+    let mut signals = Signals::new([SIGCHLD]).expect("failed to register SIGCHLD handler");
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            reap_orphans();
+        }
+    });
+}
+
+/// Fork a child rev will supervise. In the parent the child's pidfd is
+/// recorded before this returns; call [`watch`] once the child's state is
+/// recorded to be told when it exits. The child must not touch supervision.
+pub fn fork() -> nix::Result<ForkResult> {
+    let mut children = CHILDREN.lock().expect("children lock poisoned");
+    let result = unsafe { nix::unistd::fork() }?;
+    if let ForkResult::Parent { child } = result {
+        let pid = child.as_raw() as u32;
+        match pidfd_open(pid) {
+            Ok(fd) => {
+                children.insert(pid, Arc::new(fd));
+            }
+            // Without a pidfd, watch() falls back to waiting on the PID.
+            Err(e) => eprintln!("rev: pidfd_open({}) failed: {}", pid, e),
+        }
+    }
+    Ok(result)
+}
+
+/// How a child ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Code(i32),
+    Signal { signal: i32, core_dumped: bool },
+}
+
+impl Exit {
+    /// The exit code, or `None` if the child was killed by a signal.
+    pub fn code(self) -> Option<i32> {
+        match self {
+            Exit::Code(code) => Some(code),
+            Exit::Signal { .. } => None,
+        }
+    }
+
+    /// `exited 0`, `killed by SIGSEGV (core dumped)`, ...
+    pub fn describe(self) -> String {
+        match self {
+            Exit::Code(code) => format!("exited {}", code),
+            Exit::Signal {
+                signal,
+                core_dumped,
+            } => {
+                let name = Signal::try_from(signal)
+                    .map(|s| s.as_str().to_string())
+                    .unwrap_or_else(|_| format!("signal {}", signal));
+                let core = if core_dumped { " (core dumped)" } else { "" };
+                format!("killed by {}{}", name, core)
+            }
+        }
+    }
+}
+
+/// Run `on_exit` with how the child ended when the supervised child `pid`
+/// exits. The handler runs on a blocking thread, so
+/// it may sleep or start other processes.
+///
+/// Without the reactor (no runtime, or the pidfd cannot be registered) a
+/// thread waits on the pidfd instead. A child without a pidfd is waited for
+/// by PID; the orphan reaper may collect it first, and then its exit is
+/// reported as a kill. Either way the child is reaped and the handler runs.
+pub fn watch(pid: u32, on_exit: impl FnOnce(Exit) + Send + 'static) {
+    let Some(fd) = pidfd(pid) else {
+        eprintln!("rev: PID {} has no pidfd, waiting on it by PID", pid);
+        thread::spawn(move || on_exit(wait_pid(pid)));
+        return;
+    };
+    let Some(runtime) = RUNTIME.get() else {
+        thread::spawn(move || on_exit(reap(pid, &fd)));
+        return;
+    };
+    runtime.spawn(async move {
+        let exit = match AsyncFd::new(fd.as_raw_fd()) {
+            Ok(ready) => {
+                // Readable once the process has exited.
+                let _ = ready.readable().await;
+                reap(pid, &fd)
+            }
+            Err(e) => {
+                eprintln!(
+                    "rev: cannot watch PID {} in the reactor ({}), waiting on a thread",
+                    pid, e
+                );
+                // reap() blocks in waitid until the child exits.
+                let _ = tokio::task::spawn_blocking(move || on_exit(reap(pid, &fd))).await;
+                return;
+            }
+        };
+        let _ = tokio::task::spawn_blocking(move || on_exit(exit)).await;
+    });
+}
+
+/// Block until the unsupervised child `pid` exits and reap it.
+fn wait_pid(pid: u32) -> Exit {
+    loop {
+        match waitpid(nix::unistd::Pid::from_raw(pid as i32), None) {
+            Ok(WaitStatus::Exited(_, status)) => return Exit::Code(status),
+            Ok(WaitStatus::Signaled(_, signal, core_dumped)) => {
+                return Exit::Signal {
+                    signal: signal as i32,
+                    core_dumped,
+                };
+            }
+            Err(nix::errno::Errno::EINTR) => continue,
+            // Reaped elsewhere; report it as killed rather than clean.
+            Ok(_) | Err(_) => {
+                return Exit::Signal {
+                    signal: libc::SIGKILL,
+                    core_dumped: false,
+                };
+            }
+        }
+    }
+}
+
+/// Reap the exited child behind `fd` and drop its entry, then collect any
+/// orphans that queued up behind it.
+fn reap(pid: u32, fd: &OwnedFd) -> Exit {
+    let exit = loop {
+        match waitid(Id::PIDFd(fd.as_fd()), WaitPidFlag::WEXITED) {
+            Ok(WaitStatus::Exited(_, status)) => {
+                println!("rev: child {} exited with status {}", pid, status);
+                break Exit::Code(status);
+            }
+            Ok(WaitStatus::Signaled(_, signal, core_dumped)) => {
+                println!("rev: child {} killed by signal {:?}", pid, signal);
+                break Exit::Signal {
+                    signal: signal as i32,
+                    core_dumped,
+                };
+            }
+            Err(nix::errno::Errno::EINTR) => continue,
+            // Reaped elsewhere; report it as killed rather than clean.
+            Ok(_) | Err(_) => {
+                break Exit::Signal {
+                    signal: libc::SIGKILL,
+                    core_dumped: false,
+                };
+            }
+        }
+    };
+    CHILDREN
+        .lock()
+        .expect("children lock poisoned")
+        .remove(&pid);
+    reap_orphans();
+    exit
+}
+
+/// Reap exited children rev does not supervise. Stops at the first exited
+/// child that is supervised: its watcher reaps it, then calls this again.
+fn reap_orphans() {
+    while REAP_ORPHANS.load(Ordering::Relaxed) {
+        let peek = waitid(
+            Id::All,
+            WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT,
+        );
+        let pid = match peek {
+            Ok(WaitStatus::Exited(pid, _)) | Ok(WaitStatus::Signaled(pid, _, _)) => pid,
+            Ok(_) | Err(nix::errno::Errno::ECHILD) => return,
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(e) => {
+                eprintln!("rev: waitid error: {}", e);
+                return;
+            }
+        };
+        let children = CHILDREN.lock().expect("children lock poisoned");
+        if children.contains_key(&(pid.as_raw() as u32)) {
+            return;
+        }
+        let _ = waitpid(pid, Some(WaitPidFlag::WNOHANG));
+    }
+}
+
+/// Send `signal` to a supervised child. Returns false if `pid` is not (or no
+/// longer) a supervised child, in which case nothing is signalled.
+pub fn signal(pid: u32, signal: Signal) -> bool {
+    let Some(fd) = pidfd(pid) else {
+        return false;
+    };
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            fd.as_raw_fd(),
+            signal as libc::c_int,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    rc == 0
+}
+
+/// Wait up to `timeout` for a supervised child to exit. Returns true once it
+/// has exited (or is not supervised), false on timeout.
+pub fn wait_exit(pid: u32, timeout: Duration) -> bool {
+    let Some(fd) = pidfd(pid) else {
+        return true;
+    };
+    let mut pfd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        let rc = unsafe { libc::poll(&mut pfd, 1, left.as_millis().min(i32::MAX as u128) as i32) };
+        if rc > 0 {
+            return true;
+        }
+        if rc == 0 || std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return false;
+        }
+    }
+}
+
+/// Run a short-lived command (a hook) to completion. The child is recorded as
+/// supervised while it runs, so the orphan reaper leaves it to `wait()`.
+pub fn run_command(cmd: &mut std::process::Command) -> std::io::Result<std::process::ExitStatus> {
+    let mut child = spawn_command(cmd)?;
+    let status = child.wait();
+    forget_command(&child);
+    status
+}
+
+/// [`run_command`], but a command still running after `timeout` is killed;
+/// `None` then.
+pub fn run_command_timeout(
+    cmd: &mut std::process::Command,
+    timeout: Duration,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let mut child = spawn_command(cmd)?;
+    let finished = wait_exit(child.id(), timeout);
+    if !finished {
+        let _ = child.kill();
+    }
+    let status = child.wait();
+    forget_command(&child);
+    status.map(|s| finished.then_some(s))
+}
+
+fn spawn_command(cmd: &mut std::process::Command) -> std::io::Result<std::process::Child> {
+    let mut children = CHILDREN.lock().expect("children lock poisoned");
+    let child = cmd.spawn()?;
+    if let Ok(fd) = pidfd_open(child.id()) {
+        children.insert(child.id(), Arc::new(fd));
+    }
+    Ok(child)
+}
+
+/// Drop a reaped command's entry, then collect orphans that queued behind it.
+fn forget_command(child: &std::process::Child) {
+    CHILDREN
+        .lock()
+        .expect("children lock poisoned")
+        .remove(&child.id());
+    reap_orphans();
+}
+
+/// Fork a supervised child and block the calling thread until it exits.
+/// Returns `ForkResult::Child` in the child; the parent only returns once the
+/// child has been reaped.
+pub fn fork_and_wait() -> nix::Result<ForkResult> {
+    let result = fork()?;
+    if let ForkResult::Parent { child } = result {
+        let (tx, rx) = std::sync::mpsc::channel();
+        watch(child.as_raw() as u32, move |_| {
+            let _ = tx.send(());
+        });
+        let _ = rx.recv();
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn exits_are_seen_and_reaped_pids_are_never_signalled() {
+        let _ = RUNTIME.set(tokio::runtime::Handle::current());
+        let pid = match fork().unwrap() {
+            ForkResult::Child => {
+                std::thread::sleep(Duration::from_secs(30));
+                unsafe { libc::_exit(0) }
+            }
+            ForkResult::Parent { child } => child.as_raw() as u32,
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        watch(pid, move |code| {
+            let _ = tx.send(code);
+        });
+
+        assert!(!wait_exit(pid, Duration::from_millis(50)));
+        assert!(signal(pid, Signal::SIGTERM));
+        let exit = rx.await.unwrap();
+        assert_eq!(
+            exit,
+            Exit::Signal {
+                signal: libc::SIGTERM,
+                core_dumped: false
+            }
+        );
+        assert_eq!(exit.describe(), "killed by SIGTERM");
+        assert!(wait_exit(pid, Duration::ZERO));
+        // Reaped: the PID may be recycled, so it must not be signalled again.
+        assert!(!signal(pid, Signal::SIGTERM));
+        assert!(pidfd(pid).is_none());
+    }
+
+    #[test]
+    fn a_child_without_a_pidfd_is_still_reaped() {
+        // Forked around supervision, so it has no pidfd entry.
+        let pid = match unsafe { nix::unistd::fork() }.unwrap() {
+            ForkResult::Child => unsafe { libc::_exit(7) },
+            ForkResult::Parent { child } => child.as_raw() as u32,
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        watch(pid, move |exit| {
+            let _ = tx.send(exit);
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(Exit::Code(7)));
+        // Reaped, not left as a zombie.
+        assert!(
+            waitpid(
+                nix::unistd::Pid::from_raw(pid as i32),
+                Some(WaitPidFlag::WNOHANG)
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn commands_run_to_completion() {
+        let status = run_command(std::process::Command::new("true").arg("x")).unwrap();
+        assert!(status.success());
+        let mut sleep = std::process::Command::new("sleep");
+        sleep.arg("5");
+        let killed = run_command_timeout(&mut sleep, Duration::from_millis(50)).unwrap();
+        assert!(killed.is_none());
+    }
+}
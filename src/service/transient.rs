@@ -0,0 +1,129 @@
+//! Transient services: an ad-hoc command run under supervision (`rev run`).
+//!
+//! A `RunTransient` request becomes a service with no .rsc file. rev registers
+//! a `ServiceInfo` built from the request and starts it like any other system
+//! service, so it gets a log, a cgroup with the requested limits, a run
+//! history and a row in `ListServices` and the dashboard, and `rev stop` stops
+//! it. The entry leaves the table once the command exits and its restart
+//! policy does not bring it back. A reload never touches it.
+
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+
+use crate::bus::protocol::ResourceLimits;
+use crate::init::services;
+use crate::parser::{RestartPolicy, ServiceConfig, ServiceInfo};
+
+/// Whether `name` can name a transient service (and its log and cgroup).
+/// `rev` is rev's own log.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "rev"
+        && Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Build the config of a transient service from a `RunTransient` request.
+pub fn config(
+    name: &str,
+    command: &[String],
+    env: &HashMap<String, String>,
+    user: Option<&str>,
+    limits: &ResourceLimits,
+    restart_policy: Option<&str>,
+) -> Result<ServiceConfig, String> {
+    if !valid_name(name) {
+        return Err(format!("invalid transient service name '{}'", name));
+    }
+    if command.is_empty() {
+        return Err("no command to run".to_string());
+    }
+    let restart_policy = match restart_policy {
+        None => RestartPolicy::Never,
+        Some(policy) => RestartPolicy::deserialize(
+            IntoDeserializer::<serde::de::value::Error>::into_deserializer(policy),
+        )
+        .map_err(|e| format!("invalid restart policy: {}", e))?,
+    };
+    let exec_start = shell_words::join(command);
+    Ok(ServiceConfig {
+        name: name.to_string(),
+        description: Some(format!("Transient: {}", exec_start)),
+        exec_start,
+        env: env.iter().collect(),
+        user: user.map(str::to_string),
+        restart_policy,
+        memory_max_mb: limits.memory_max_mb,
+        cpu_quota: limits.cpu_quota,
+        tasks_max: limits.tasks_max,
+        ..Default::default()
+    })
+}
+
+/// Register `config` as a transient service and start it. Returns the PID, or
+/// `None` when the command was already gone by the time it was asked for.
+pub fn run(config: ServiceConfig) -> Result<Option<u32>, String> {
+    let name = config.name.clone();
+    let registered = services::register_new(
+        name.clone(),
+        ServiceInfo {
+            name: name.clone(),
+            transient: true,
+            config: config.clone(),
+            ..Default::default()
+        },
+    );
+    if !registered {
+        return Err(format!("service '{}' already exists", name));
+    }
+    crate::logger::write_log(
+        &name,
+        &format!("Transient service for '{}'", config.exec_start),
+    );
+
+    if super::spawn_running(&config, None) != super::Spawn::Launched {
+        services::deregister_service(&name);
+        super::cgroup::remove(&name);
+        return Err(format!("{} failed to start; see its log", name));
+    }
+    Ok(services::get_service(&name).and_then(|info| info.pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_becomes_a_config() {
+        let command = ["/bin/sh", "-c", "make check"].map(String::from);
+        let env = HashMap::from([("CI".to_string(), "1".to_string())]);
+        let limits = ResourceLimits {
+            memory_max_mb: Some(512),
+            ..Default::default()
+        };
+        let config = config(
+            "ci/build-42",
+            &command,
+            &env,
+            Some("builder"),
+            &limits,
+            Some("on-failure"),
+        )
+        .unwrap();
+        assert_eq!(config.exec_start, "/bin/sh -c 'make check'");
+        assert_eq!(config.env.0, env);
+        assert_eq!(config.user.as_deref(), Some("builder"));
+        assert_eq!(config.restart_policy, RestartPolicy::OnFailure);
+        assert_eq!((config.memory_max_mb, config.tasks_max), (Some(512), None));
+
+        let none = ResourceLimits::default();
+        assert!(super::config("../escape", &command, &env, None, &none, None).is_err());
+        assert!(super::config("rev", &command, &env, None, &none, None).is_err());
+        assert!(super::config("job", &[], &env, None, &none, None).is_err());
+        assert!(super::config("job", &command, &env, None, &none, Some("sometimes")).is_err());
+    }
+}
@@ -0,0 +1,744 @@
+//! Supervised `scope = "user"` services, one table per User Lane.
+//!
+//! When a user's Lane comes up, their user-scope services (the system's
+//! user-scope defaults plus those installed in their vault) are loaded into a
+//! table of the lane's own, started in dependency order as the user, with
+//! `WIREBUS_SOCKET` pointed at the lane. Each entry is a [`ServiceInfo`], like
+//! the system table's: restart policies and their start limits apply when a
+//! process exits, output and
+//! lifecycle lines go to the user's own log, and the user starts, stops and
+//! lists the services with the ordinary service messages on their Lane. Names
+//! are per user, so the tables never collide with each other or with the
+//! system's. Tearing the lane down stops its services and drops its table.
+//!
+//! While the lane is up, its vault service directory is watched
+//! (`init::watch`): a new file is added and started, an edited one replaces
+//! its entry's definition (restarting it if it runs), and a removed one is
+//! stopped and dropped.
+//!
+//! A service is ready once its process runs: a dependent listed in `requires`
+//! is not started while a dependency on the same lane is not running.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use nix::sys::signal::Signal;
+use once_cell::sync::Lazy;
+
+use super::supervisor;
+use crate::parser::{RestartPolicy, ServiceConfig, ServiceInfo, ServiceScope, ServiceState};
+
+/// One lane's services.
+struct Table {
+    gid: u32,
+    lane_socket: PathBuf,
+    services: BTreeMap<String, ServiceInfo>,
+    /// Recent policy restarts per service, against its start limit.
+    restarts: HashMap<String, Vec<Instant>>,
+}
+
+/// uid -> that user's service table, while their Lane is up.
+static TABLES: Lazy<Mutex<HashMap<u32, Table>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn tables() -> std::sync::MutexGuard<'static, HashMap<u32, Table>> {
+    TABLES.lock().expect("user service tables lock poisoned")
+}
+
+/// The log a user service writes to, in the user's runtime dir.
+pub fn log_path(uid: u32, name: &str) -> PathBuf {
+    let dir = if cfg!(debug_assertions) {
+        format!("./Transit/Ephemeral/user/{}/log", uid)
+    } else {
+        format!("/Transit/Ephemeral/user/{}/log", uid)
+    };
+    Path::new(&dir).join(format!("{}.log", name.replace('/', "_")))
+}
+
+/// Open (creating) a user service's log, owned by the user so the service can
+/// keep writing to it. Ownership is best effort (non-root dev builds).
+fn open_log(uid: u32, gid: u32, name: &str) -> io::Result<File> {
+    let path = log_path(uid, name);
+    let dir = path.parent().expect("log path has a parent");
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    let _ = std::os::unix::fs::chown(dir, Some(uid), Some(gid));
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(&path)?;
+    let _ = std::os::unix::fs::chown(&path, Some(uid), Some(gid));
+    Ok(file)
+}
+
+/// Append a timestamped lifecycle line to a user service's log.
+fn write_log(uid: u32, gid: u32, name: &str, message: &str) {
+    if let Ok(mut f) = open_log(uid, gid, name) {
+        let ts = Utc::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        let _ = writeln!(f, "[{}] {}", ts, message);
+    }
+}
+
+/// Load a user's `scope=user` services into their lane's table and start them,
+/// dependency-ordered. Called when their Lane comes up. Services already in the
+/// table (a second login while the lane is up) are left as they are.
+pub fn load(uid: u32, gid: u32, lane_socket: &Path, account_uuid: &str) {
+    let mut candidates: Vec<(String, ServiceConfig, PathBuf)> = Vec::new();
+    for dir in crate::parser::service_dirs() {
+        collect_user_scope(&dir, &mut candidates);
+    }
+    collect_user_scope(
+        &crate::bus::lanes::user_service_dir(account_uuid),
+        &mut candidates,
+    );
+
+    let mut sortable: Vec<(String, ServiceConfig)> = Vec::new();
+    {
+        let mut tables = tables();
+        let table = tables.entry(uid).or_insert_with(|| Table {
+            gid,
+            lane_socket: lane_socket.to_path_buf(),
+            services: BTreeMap::new(),
+            restarts: HashMap::new(),
+        });
+        for (name, config, path) in candidates {
+            if table.services.contains_key(&name) {
+                continue;
+            }
+            table
+                .services
+                .insert(name.clone(), new_info(&name, config.clone(), &path));
+            sortable.push((name, config));
+        }
+    }
+
+    let (order, forced) = crate::init::ordering::start_order(&sortable);
+    if !forced.is_empty() {
+        eprintln!(
+            "rev: dependency cycle among user services {:?} of uid {}; started in a forced order",
+            forced, uid
+        );
+    }
+    for idx in order {
+        if let Err(e) = start(uid, &sortable[idx].0) {
+            eprintln!("rev: user service {} (uid {}): {}", sortable[idx].0, uid, e);
+        }
+    }
+}
+
+/// Add one newly installed `scope=user` service to `uid`'s table and start it.
+/// Called when a file appears in the user's vault service directory while
+/// their Lane is up, so `rev install --user` takes effect without a re-login.
+pub fn add(uid: u32, path: &Path) {
+    let config = match fs::read_to_string(path)
+        .ok()
+        .and_then(|t| crate::parser::deserialize_service_config(&t).ok())
+    {
+        Some(c) if c.scope == ServiceScope::User => c,
+        Some(_) => return,
+        None => {
+            eprintln!(
+                "rev: user service {}: could not parse, not started",
+                path.display()
+            );
+            return;
+        }
+    };
+    let name = config.name.clone();
+    {
+        let mut tables = tables();
+        let Some(table) = tables.get_mut(&uid) else {
+            return;
+        };
+        table
+            .services
+            .entry(name.clone())
+            .or_insert_with(|| new_info(&name, config, path));
+    }
+    if let Err(e) = start(uid, &name) {
+        eprintln!("rev: user service {} (uid {}): {}", name, uid, e);
+    }
+}
+
+/// Apply an edit of the service file at `path` to `uid`'s table. The service
+/// loaded from it takes the new definition and is restarted if it is running.
+/// A file that now names another service, or is no longer user-scope, is a
+/// removal of the old service followed by an add.
+pub fn update(uid: u32, path: &Path) {
+    let Some(config) = fs::read_to_string(path)
+        .ok()
+        .and_then(|t| crate::parser::deserialize_service_config(&t).ok())
+    else {
+        eprintln!(
+            "rev: user service {}: could not parse, definition kept",
+            path.display()
+        );
+        return;
+    };
+    let (name, running) = {
+        let mut tables = tables();
+        let Some(table) = tables.get_mut(&uid) else {
+            return;
+        };
+        let gid = table.gid;
+        let Some(info) = table.services.values_mut().find(|i| loaded_from(i, path)) else {
+            drop(tables);
+            return add(uid, path);
+        };
+        if info.name != config.name || config.scope != ServiceScope::User {
+            drop(tables);
+            remove(uid, path);
+            return add(uid, path);
+        }
+        if info.config == config {
+            return;
+        }
+        info.config = config;
+        write_log(uid, gid, &info.name, "Definition changed");
+        (info.name.clone(), info.pid.is_some())
+    };
+    if running && let Err(e) = restart(uid, &name) {
+        eprintln!("rev: user service {} (uid {}): {}", name, uid, e);
+    }
+}
+
+/// Drop the services of `uid`'s table loaded from `path` (or from under it,
+/// when a directory was removed), stopping those that run.
+pub fn remove(uid: u32, path: &Path) {
+    let (gid, removed) = {
+        let mut tables = tables();
+        let Some(table) = tables.get_mut(&uid) else {
+            return;
+        };
+        let names: Vec<String> = table
+            .services
+            .values()
+            .filter(|i| loaded_from(i, path))
+            .map(|i| i.name.clone())
+            .collect();
+        let removed: Vec<ServiceInfo> = names
+            .iter()
+            .filter_map(|n| table.services.remove(n))
+            .collect();
+        (table.gid, removed)
+    };
+    for info in removed {
+        if let Some(pid) = info.pid {
+            write_log(uid, gid, &info.name, "Stopping service (file removed)");
+            terminate(pid, info.config.stop_timeout());
+        }
+    }
+}
+
+/// Whether `info` was loaded from `path`, or from a file under it.
+fn loaded_from(info: &ServiceInfo, path: &Path) -> bool {
+    info.config_path
+        .as_deref()
+        .is_some_and(|p| Path::new(p).starts_with(path))
+}
+
+fn new_info(name: &str, config: ServiceConfig, path: &Path) -> ServiceInfo {
+    ServiceInfo {
+        name: name.to_string(),
+        config_path: Some(path.display().to_string()),
+        config,
+        ..Default::default()
+    }
+}
+
+/// Collect parseable `scope=user` services from `dir` into `out`.
+fn collect_user_scope(dir: &Path, out: &mut Vec<(String, ServiceConfig, PathBuf)>) {
+    if !dir.exists() {
+        return;
+    }
+    for entry in walkdir::WalkDir::new(dir).into_iter().flatten() {
+        let path = entry.path();
+        if path.is_file()
+            && path.extension().and_then(|s| s.to_str()) == Some("rsc")
+            && let Some(config) = fs::read_to_string(path)
+                .ok()
+                .and_then(|t| crate::parser::deserialize_service_config(&t).ok())
+            && config.scope == ServiceScope::User
+        {
+            out.push((config.name.clone(), config, path.to_path_buf()));
+        }
+    }
+}
+
+/// The services on `uid`'s lane.
+pub fn list(uid: u32) -> Vec<(String, ServiceInfo)> {
+    tables()
+        .get(&uid)
+        .map(|t| {
+            t.services
+                .iter()
+                .map(|(name, info)| (name.clone(), info.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Start service `name` on `uid`'s lane. Returns its PID; a service that is
+/// already running is left alone.
+pub fn start(uid: u32, name: &str) -> Result<u32, String> {
+    let (config, gid, lane_socket) = {
+        let mut tables = tables();
+        let table = tables
+            .get_mut(&uid)
+            .ok_or_else(|| format!("no active lane for uid {}", uid))?;
+        let info = table
+            .services
+            .get(name)
+            .ok_or_else(|| format!("service '{}' not found", name))?;
+        if let Some(pid) = info.pid {
+            return Ok(pid);
+        }
+        if info.state == ServiceState::Starting {
+            return Err(format!("service '{}' is already starting", name));
+        }
+        // Readiness: a required service on this lane must be running first.
+        if let Some(dep) = info.config.requires.iter().find(|dep| {
+            table
+                .services
+                .get(dep.as_str())
+                .is_some_and(|d| !d.is_running)
+        }) {
+            let reason = format!("required service '{}' is not running", dep);
+            set_state(table, uid, name, ServiceState::Failed, &reason);
+            return Err(reason);
+        }
+        let config = info.config.clone();
+        set_state(table, uid, name, ServiceState::Starting, "start requested");
+        (config, table.gid, table.lane_socket.clone())
+    };
+
+    let spawned = spawn(name, &config, uid, gid, &lane_socket);
+    let torn_down = {
+        let mut tables = tables();
+        match (tables.get_mut(&uid), &spawned) {
+            (None, _) => true,
+            (Some(table), Ok(pid)) => {
+                if let Some(info) = table.services.get_mut(name) {
+                    info.pid = Some(*pid);
+                    info.is_running = true;
+                    info.up_timestamp = Some(Utc::now());
+                }
+                write_log(uid, gid, name, &format!("Service started (PID {})", pid));
+                set_state(table, uid, name, ServiceState::Running, "process started");
+                false
+            }
+            (Some(table), Err(e)) => {
+                set_state(table, uid, name, ServiceState::Failed, e);
+                false
+            }
+        }
+    };
+    // Watched only once the PID is recorded, so a process that exits at once
+    // still has its exit seen.
+    if let Ok(pid) = spawned {
+        if torn_down {
+            // The lane went down while forking; take the process with it.
+            supervisor::signal(pid, Signal::SIGTERM);
+        }
+        let name = name.to_string();
+        supervisor::watch(pid, move |exit| exited(uid, &name, pid, exit));
+    }
+    if torn_down {
+        return Err(format!("lane of uid {} was torn down", uid));
+    }
+    spawned
+}
+
+/// Stop service `name` on `uid`'s lane: SIGTERM, then SIGKILL after its
+/// `timeout-stop`. Returns once the signal is sent.
+pub fn stop(uid: u32, name: &str) -> Result<(), String> {
+    let (pid, timeout) = {
+        let mut tables = tables();
+        let table = tables
+            .get_mut(&uid)
+            .ok_or_else(|| format!("no active lane for uid {}", uid))?;
+        let info = table
+            .services
+            .get(name)
+            .ok_or_else(|| format!("service '{}' not found", name))?;
+        let Some(pid) = info.pid else {
+            return Err(format!("service '{}' is not running", name));
+        };
+        let timeout = info.config.stop_timeout();
+        set_state(table, uid, name, ServiceState::Stopping, "stop requested");
+        (pid, timeout)
+    };
+    terminate(pid, timeout);
+    Ok(())
+}
+
+/// Stop service `name` if it runs, then start it again once it has exited.
+pub fn restart(uid: u32, name: &str) -> Result<(), String> {
+    let running = list(uid)
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .ok_or_else(|| format!("service '{}' not found", name))?
+        .1
+        .pid;
+    let Some(pid) = running else {
+        return start(uid, name).map(|_| ());
+    };
+    stop(uid, name)?;
+    let name = name.to_string();
+    thread::spawn(move || {
+        while !supervisor::wait_exit(pid, Duration::from_secs(1)) {}
+        if let Err(e) = start(uid, &name) {
+            eprintln!("rev: user service {} (uid {}): {}", name, uid, e);
+        }
+    });
+    Ok(())
+}
+
+/// Stop every service on `uid`'s lane and drop the table. Called when the lane
+/// is torn down; the services' exits are no longer restarted.
+pub fn stop_all(uid: u32) {
+    let Some(table) = tables().remove(&uid) else {
+        return;
+    };
+    for info in table.services.values() {
+        if let Some(pid) = info.pid {
+            write_log(
+                uid,
+                table.gid,
+                &info.name,
+                "Stopping service (lane shut down)",
+            );
+            terminate(pid, info.config.stop_timeout());
+        }
+    }
+}
+
+/// SIGTERM `pid`, and SIGKILL it if it is still there after `timeout` seconds.
+fn terminate(pid: u32, timeout: u64) {
+    supervisor::signal(pid, Signal::SIGTERM);
+    thread::spawn(move || {
+        if !supervisor::wait_exit(pid, Duration::from_secs(timeout)) {
+            supervisor::signal(pid, Signal::SIGKILL);
+        }
+    });
+}
+
+/// Move `name` to `state`, logging the transition to the user's log.
+fn set_state(table: &mut Table, uid: u32, name: &str, state: ServiceState, reason: &str) {
+    let Some(info) = table.services.get_mut(name) else {
+        return;
+    };
+    if info.state == state {
+        return;
+    }
+    let old = std::mem::replace(&mut info.state, state);
+    write_log(
+        uid,
+        table.gid,
+        name,
+        &format!("State {} -> {} ({})", old.as_str(), state.as_str(), reason),
+    );
+}
+
+/// Record that process `pid` of service `name` on `uid`'s lane exited, and
+/// restart it if its policy says so.
+fn exited(uid: u32, name: &str, pid: u32, exit: supervisor::Exit) {
+    let restart = {
+        let mut tables = tables();
+        // A lane torn down took its table; nothing is restarted.
+        let Some(table) = tables.get_mut(&uid) else {
+            return;
+        };
+        let Some(info) = table.services.get_mut(name).filter(|i| i.pid == Some(pid)) else {
+            return;
+        };
+        let exit_code = exit.code();
+        info.pid = None;
+        info.is_running = false;
+        info.up_timestamp = None;
+        info.last_exit_code = exit_code;
+        let stopping = info.state == ServiceState::Stopping;
+        let mut restart = !stopping
+            && match info.config.restart_policy {
+                RestartPolicy::Always => true,
+                RestartPolicy::OnFailure => exit_code != Some(0),
+                RestartPolicy::Never | RestartPolicy::OnResourceChange => false,
+            };
+        if restart {
+            let limit = info.config.start_limit();
+            let times = table.restarts.entry(name.to_string()).or_default();
+            if super::within_limit(times, Instant::now(), limit) {
+                info.restart_count += 1;
+            } else {
+                // Giving up clears the count, as for system services.
+                table.restarts.remove(name);
+                write_log(
+                    uid,
+                    table.gid,
+                    name,
+                    &format!(
+                        "Start limit hit ({} restarts within {}s), not restarting",
+                        limit.0,
+                        limit.1.as_secs()
+                    ),
+                );
+                restart = false;
+            }
+        }
+        let (state, reason) = match exit_code {
+            Some(0) => (ServiceState::Inactive, "exited".to_string()),
+            _ if stopping => (ServiceState::Inactive, "stopped".to_string()),
+            _ => (ServiceState::Failed, exit.describe()),
+        };
+        set_state(table, uid, name, state, &reason);
+        restart
+    };
+    if restart {
+        // Small delay to avoid tight restart loops, as for system services.
+        thread::sleep(Duration::from_millis(500));
+        if let Err(e) = start(uid, name) {
+            eprintln!(
+                "rev: user service {} (uid {}): restart failed: {}",
+                name, uid, e
+            );
+        }
+    }
+}
+
+/// Fork/exec one user service as the lane's user. The service's identity is
+/// the lane owner, never the config's `user=` field, so a user-scope service
+/// cannot ask to run as someone else.
+fn spawn(
+    name: &str,
+    config: &ServiceConfig,
+    uid: u32,
+    gid: u32,
+    lane_socket: &Path,
+) -> Result<u32, String> {
+    let args = match shell_words::split(&config.exec_start) {
+        Ok(a) if !a.is_empty() => a,
+        _ => return Err("invalid exec-start".to_string()),
+    };
+    // Opened here, owned by the user, so rev's lifecycle lines and the
+    // service's output share one file.
+    let log = open_log(uid, gid, name).map_err(|e| format!("cannot open log: {}", e))?;
+    let settings = crate::settings::get();
+
+    match supervisor::fork() {
+        Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
+            println!(
+                "rev: started user service {} (PID {}, uid {})",
+                name, child, uid
+            );
+            Ok(child.as_raw() as u32)
+        }
+        #[allow(unreachable_code)]
+        Ok(nix::unistd::ForkResult::Child) => {
+            use std::os::unix::io::AsRawFd;
+            unsafe {
+                libc::dup2(log.as_raw_fd(), libc::STDOUT_FILENO);
+                libc::dup2(log.as_raw_fd(), libc::STDERR_FILENO);
+                let grp = [gid as libc::gid_t];
+                if libc::setgroups(1, grp.as_ptr()) != 0
+                    || libc::setgid(gid as libc::gid_t) != 0
+                    || libc::setuid(uid as libc::uid_t) != 0
+                {
+                    eprintln!(
+                        "rev: user service {}: failed to drop to {}:{}",
+                        name, uid, gid
+                    );
+                    std::process::exit(1);
+                }
+                // Minimal environment: point the service at its lane bus, then
+                // layer the service's own env on top.
+                std::env::set_var("WIREBUS_SOCKET", lane_socket);
+                std::env::set_var("PATH", &settings.path);
+                for (key, value) in &settings.env {
+                    std::env::set_var(key, value);
+                }
+                for (key, value) in &config.env {
+                    std::env::set_var(key, value);
+                }
+            }
+            if let Some(ref dir) = config.working_dir {
+                let _ = nix::unistd::chdir(dir.as_path());
+            }
+            use std::ffi::CString;
+            let cstr: Vec<CString> = args
+                .iter()
+                .map(|a| CString::new(a.clone()).expect("invalid argument"))
+                .collect();
+            let refs: Vec<&std::ffi::CStr> = cstr.iter().map(|s| s.as_c_str()).collect();
+            nix::unistd::execv(&cstr[0], &refs).expect("execv failed");
+            unreachable!()
+        }
+        Err(e) => Err(format!("fork failed: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_user_scope_takes_only_user_services() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, scope: ServiceScope| {
+            let cfg = ServiceConfig {
+                name: name.to_string(),
+                exec_start: "/bin/true".to_string(),
+                scope,
+                ..Default::default()
+            };
+            let toml = crate::parser::serialize_service_config(&cfg).unwrap();
+            std::fs::write(dir.path().join(format!("{name}.rsc")), toml).unwrap();
+        };
+        write("a-user", ServiceScope::User);
+        write("b-system", ServiceScope::System);
+
+        let mut out = Vec::new();
+        collect_user_scope(dir.path(), &mut out);
+        let names: Vec<&str> = out.iter().map(|(n, _, _)| n.as_str()).collect();
+        assert_eq!(names, ["a-user"]);
+    }
+
+    #[test]
+    fn lane_table_tracks_exits_and_dependencies() {
+        // A uid no test lane uses; pids that are not ours to signal.
+        let uid = 4_000_123;
+        let service = |name: &str, requires: &[&str]| ServiceConfig {
+            name: name.to_string(),
+            exec_start: "/bin/true".to_string(),
+            scope: ServiceScope::User,
+            requires: requires.iter().map(|r| r.to_string()).collect(),
+            ..Default::default()
+        };
+        let mut services = BTreeMap::new();
+        for config in [service("sync", &[]), service("indexer", &["sync"])] {
+            let name = config.name.clone();
+            let mut info = new_info(&name, config, Path::new("/dev/null"));
+            if name == "sync" {
+                info.pid = Some(u32::MAX);
+                info.is_running = true;
+                info.state = ServiceState::Running;
+            }
+            services.insert(name, info);
+        }
+        tables().insert(
+            uid,
+            Table {
+                gid: uid,
+                lane_socket: PathBuf::from("/dev/null"),
+                services,
+                restarts: HashMap::new(),
+            },
+        );
+
+        exited(uid, "sync", u32::MAX, supervisor::Exit::Code(3));
+        let sync = list(uid).into_iter().find(|(n, _)| n == "sync").unwrap().1;
+        assert_eq!(
+            (sync.state, sync.pid, sync.last_exit_code),
+            (ServiceState::Failed, None, Some(3))
+        );
+
+        // Its dependent is not started while it is down.
+        assert!(start(uid, "indexer").unwrap_err().contains("'sync'"));
+        assert!(stop(uid, "sync").is_err());
+
+        stop_all(uid);
+        assert!(list(uid).is_empty());
+        let _ = fs::remove_dir_all(log_path(uid, "sync").parent().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn restarts_stop_at_the_start_limit() {
+        let uid = 4_000_125;
+        // No restarts allowed at all, so the exit is never restarted.
+        let config = ServiceConfig {
+            name: "sync".to_string(),
+            exec_start: "/bin/true".to_string(),
+            scope: ServiceScope::User,
+            restart_policy: RestartPolicy::Always,
+            start_limit_burst: Some(0),
+            ..Default::default()
+        };
+        let mut info = new_info("sync", config, Path::new("/dev/null"));
+        info.pid = Some(u32::MAX);
+        info.is_running = true;
+        info.state = ServiceState::Running;
+        let mut services = BTreeMap::new();
+        services.insert("sync".to_string(), info);
+        tables().insert(
+            uid,
+            Table {
+                gid: uid,
+                lane_socket: PathBuf::from("/dev/null"),
+                services,
+                restarts: HashMap::new(),
+            },
+        );
+
+        exited(uid, "sync", u32::MAX, supervisor::Exit::Code(1));
+        let (_, sync) = list(uid).into_iter().next().unwrap();
+        assert_eq!(
+            (sync.state, sync.pid, sync.restart_count),
+            (ServiceState::Failed, None, 0)
+        );
+        let log = fs::read_to_string(log_path(uid, "sync")).unwrap();
+        assert!(log.contains("Start limit hit"));
+
+        stop_all(uid);
+        let _ = fs::remove_dir_all(log_path(uid, "sync").parent().unwrap().parent().unwrap());
+    }
+
+    #[test]
+    fn edits_and_removals_apply_to_the_lane_table() {
+        let uid = 4_000_124;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sync.rsc");
+        let mut config = ServiceConfig {
+            name: "sync".to_string(),
+            exec_start: "/bin/true".to_string(),
+            scope: ServiceScope::User,
+            ..Default::default()
+        };
+        let mut services = BTreeMap::new();
+        services.insert("sync".to_string(), new_info("sync", config.clone(), &path));
+        tables().insert(
+            uid,
+            Table {
+                gid: uid,
+                lane_socket: PathBuf::from("/dev/null"),
+                services,
+                restarts: HashMap::new(),
+            },
+        );
+
+        // A stopped service takes the new definition and stays stopped.
+        config.exec_start = "/bin/false".to_string();
+        let toml = crate::parser::serialize_service_config(&config).unwrap();
+        fs::write(&path, toml).unwrap();
+        update(uid, &path);
+        let (_, sync) = list(uid).into_iter().next().unwrap();
+        assert_eq!(
+            (sync.config.exec_start.as_str(), sync.pid),
+            ("/bin/false", None)
+        );
+
+        // Removing its directory drops it.
+        remove(uid, dir.path());
+        assert!(list(uid).is_empty());
+
+        stop_all(uid);
+        let _ = fs::remove_dir_all(log_path(uid, "sync").parent().unwrap().parent().unwrap());
+    }
+}
@@ -0,0 +1,131 @@
+//! Lingering users: accounts whose User Lane and `scope=user` services run
+//! without a session.
+//!
+//! The flag is one empty file per uid under `/Vault/State/rev/linger/`, rev's
+//! own record (UAC has no field for it); keyed by uid, so it survives a rename.
+//! At boot rev brings up each lingering user's Lane and starts their services
+//! as if they had logged in. When their last session ends, the lane stays up.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where the linger flags live.
+fn dir() -> PathBuf {
+    if cfg!(debug_assertions) {
+        PathBuf::from("./Vault/State/rev/linger")
+    } else {
+        PathBuf::from("/Vault/State/rev/linger")
+    }
+}
+
+/// Whether `uid` lingers.
+pub fn is_lingering(uid: u32) -> bool {
+    flagged(&dir(), uid)
+}
+
+/// Every lingering uid, in order.
+pub fn list() -> Vec<u32> {
+    flagged_uids(&dir())
+}
+
+/// Whether the flag directory `dir` holds a flag for `uid`.
+fn flagged(dir: &Path, uid: u32) -> bool {
+    dir.join(uid.to_string()).exists()
+}
+
+/// The uids flagged in `dir`, in order.
+fn flagged_uids(dir: &Path) -> Vec<u32> {
+    let mut uids: Vec<u32> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse().ok())
+        .collect();
+    uids.sort_unstable();
+    uids
+}
+
+/// Turn lingering on or off for `uid`, and bring its Lane up or down to match
+/// when the user has no session. Must be called from within the tokio runtime
+/// (starting a lane spawns its server).
+pub fn set(uid: u32, enable: bool) -> Result<(), String> {
+    let dir = dir();
+    set_flag(&dir, uid, enable).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    crate::logger::write_log(
+        "rev",
+        &format!(
+            "linger {} for uid {}",
+            if enable { "enabled" } else { "disabled" },
+            uid
+        ),
+    );
+
+    let has_session = super::list_sessions().iter().any(|s| s.uid == uid);
+    if enable && !crate::bus::lanes::LANES.is_active(uid) {
+        start_lane(uid)?;
+    } else if !enable && !has_session {
+        let _ = crate::bus::lanes::LANES.stop_lane(uid);
+    }
+    Ok(())
+}
+
+/// Set or clear `uid`'s flag in the flag directory `dir`.
+fn set_flag(dir: &Path, uid: u32, enable: bool) -> io::Result<()> {
+    let flag = dir.join(uid.to_string());
+    if enable {
+        fs::create_dir_all(dir)?;
+        return fs::write(&flag, "");
+    }
+    match fs::remove_file(&flag) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+/// Bring up the Lane and user services of every lingering user. Boot only.
+pub fn start_all() {
+    for uid in list() {
+        if let Err(e) = start_lane(uid) {
+            eprintln!("rev: lingering uid {}: {}", uid, e);
+        }
+    }
+}
+
+/// Start `uid`'s Lane, its user services and the watch on its vault services,
+/// as a login would.
+fn start_lane(uid: u32) -> Result<(), String> {
+    let uac = uac_core::Uac::open().map_err(|e| format!("open UAC: {e}"))?;
+    let account = uac
+        .name_by_uid(uid)
+        .map_err(|e| format!("{e:#}"))?
+        .ok_or_else(|| format!("uid {uid} is not a UAC account"))
+        .and_then(|name| uac.get(&name).map_err(|e| format!("{e:#}")))?;
+    let socket = crate::bus::lanes::LANES.start_lane(uid)?;
+    println!("rev: uid {} lingers; lane and user services started", uid);
+    crate::service::user_services::load(uid, account.gid, &socket, &account.uuid);
+    crate::init::watch::watch_lane(uid, &crate::bus::lanes::user_service_dir(&account.uuid));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linger_flags_are_kept_per_uid() {
+        let root = tempfile::tempdir().unwrap();
+        // Created with the first flag.
+        let dir = root.path().join("linger");
+        assert!(flagged_uids(&dir).is_empty());
+        set_flag(&dir, 1001, true).unwrap();
+        set_flag(&dir, 1001, true).unwrap();
+        set_flag(&dir, 42, true).unwrap();
+        assert!(flagged(&dir, 1001));
+        assert_eq!(flagged_uids(&dir), [42, 1001]);
+        set_flag(&dir, 1001, false).unwrap();
+        set_flag(&dir, 1001, false).unwrap();
+        assert!(!flagged(&dir, 1001));
+        assert_eq!(flagged_uids(&dir), [42]);
+    }
+}
@@ -13,6 +13,8 @@
 //!    Rook Guard, sanitizes the environment, and fork()/exec()s the
 //!    command as the requested user. No setuid binaries needed.
 
+pub mod linger;
+
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -66,8 +68,11 @@ pub fn start_session(
         format!("/Space/{}", username)
     };
 
+    // Read before the fork: the child must not take rev's locks.
+    let path = crate::settings::get().path.clone();
+
     // Fork and drop privileges
-    match unsafe { nix::unistd::fork() } {
+    match crate::service::supervisor::fork() {
         Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
             let pid = child.as_raw() as u32;
             let session = Session {
@@ -85,12 +90,7 @@ pub fn start_session(
                 .expect("sessions lock poisoned")
                 .insert(session_id, session.clone());
 
-            // Track as a running process for zombie reaping
-            crate::init::services::update_service_pid(
-                None, // not a named service
-                None,
-                None,
-            );
+            crate::service::supervisor::watch(pid, move |_| handle_session_exit(pid));
 
             crate::logger::write_log(
                 "rev",
@@ -122,7 +122,7 @@ pub fn start_session(
                 std::env::set_var("USER", username);
                 std::env::set_var("LOGNAME", username);
                 std::env::set_var("SHELL", "/Core/Bin/nushell");
-                std::env::set_var("PATH", "/Core/Bin:/Construct/Bin");
+                std::env::set_var("PATH", &path);
                 std::env::set_var("XDG_RUNTIME_DIR", format!("/Transit/Ephemeral/user/{}", uid));
                 std::env::set_var(
                     "WIREBUS_SOCKET",
@@ -192,8 +192,9 @@ pub fn exec_as(
     // Resolve the target's real identity through UAC (gid/home/shell), not a
     // gid==uid guess. Done before the fork so failures report cleanly.
     let target = crate::auth::resolve_target(target_uid)?;
+    let path = crate::settings::get().path.clone();
 
-    match unsafe { nix::unistd::fork() } {
+    match crate::service::supervisor::fork() {
         Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
             let pid = child.as_raw() as u32;
             crate::logger::write_log(
@@ -203,6 +204,12 @@ pub fn exec_as(
                     pid, command, target_uid
                 ),
             );
+            crate::service::supervisor::watch(pid, move |exit| {
+                crate::logger::write_log(
+                    "rev",
+                    &format!("ExecAs: PID {} {}", pid, exit.describe()),
+                );
+            });
             Ok(pid)
         }
         #[allow(unreachable_code)]
@@ -233,7 +240,7 @@ pub fn exec_as(
                 std::env::set_var("LOGNAME", &target.name);
                 std::env::set_var("HOME", &target.home);
                 std::env::set_var("SHELL", &target.shell);
-                std::env::set_var("PATH", "/Core/Bin:/Construct/Bin");
+                std::env::set_var("PATH", &path);
                 for (key, value) in env {
                     if crate::auth::env_allowed(key) {
                         std::env::set_var(key, value);
@@ -281,9 +288,7 @@ pub fn end_session(session_id: u64) -> Result<(), String> {
     );
 
     // Kill the session process
-    unsafe {
-        libc::kill(session.pid as i32, libc::SIGTERM);
-    }
+    crate::service::supervisor::signal(session.pid, nix::sys::signal::Signal::SIGTERM);
 
     // Clean up seat devices
     crate::seat::close_all_devices(session_id);
@@ -334,9 +339,9 @@ pub fn list_sessions() -> Vec<Session> {
         .collect()
 }
 
-/// Called when a session process exits (from zombie reaper).
+/// Called when a session process exits (from its supervision watch).
 /// Cleans up the session state.
-pub fn handle_session_exit(pid: u32) {
+fn handle_session_exit(pid: u32) {
     let mut sessions = SESSIONS.lock().expect("sessions lock poisoned");
     let session_id = sessions
         .iter()
@@ -348,8 +353,11 @@ pub fn handle_session_exit(pid: u32) {
         if let Some(s) = session {
             println!("rev: session {} for {} exited", id, s.username);
             crate::seat::close_all_devices(id);
-            // The user is gone; tear down their Lane bus too.
-            let _ = crate::bus::lanes::LANES.stop_lane(s.uid);
+            // The user is gone; tear down their Lane bus too, unless they
+            // linger.
+            if !linger::is_lingering(s.uid) {
+                let _ = crate::bus::lanes::LANES.stop_lane(s.uid);
+            }
         }
     }
 }
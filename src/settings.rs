@@ -0,0 +1,193 @@
+//! rev's own settings, from `rev.toml`.
+//!
+//! Read from `/Construct/Config/rev.toml` if there is one, else from
+//! `/Core/Config/rev.toml`: the same precedence as the config overlay, and it
+//! holds before the overlay is mounted too. Every key is optional and defaults
+//! to rev's built-in value; unknown keys are an error, so a typo is reported
+//! rather than ignored. The file is read at boot and again by each reload
+//! (`rev reload`). A file that fails to parse or validate leaves the settings
+//! in force unchanged.
+//!
+//! Settings are read where they are used, so most changes apply from the next
+//! use. `max-bus-connections` is the exception: a bus reads it when it starts.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    /// Connections a bus accepts at once.
+    pub max_bus_connections: usize,
+    /// Size at which a log file is rotated, in MiB.
+    pub max_log_size_mb: u64,
+    /// Rotated log files kept per log.
+    pub max_log_files: u32,
+    /// Seconds a Lookup waits for a bus-activated service to register.
+    pub activation_timeout_sec: u64,
+    /// `timeout-stop` of services that set none, in seconds.
+    pub timeout_stop: u64,
+    /// Shell of the root console when no account exists.
+    pub console_shell: String,
+    /// `PATH` of the console.
+    pub console_path: String,
+    /// `PATH` of sessions and user services.
+    pub path: String,
+    /// Environment given to every service and its hooks, under its own `env`.
+    pub env: HashMap<String, String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_bus_connections: 1024,
+            max_log_size_mb: 10,
+            max_log_files: 5,
+            activation_timeout_sec: 5,
+            timeout_stop: 10,
+            console_shell: "/Core/Bin/brush".to_string(),
+            console_path: "/Core/Bin".to_string(),
+            path: "/Core/Bin:/Construct/Bin".to_string(),
+            env: HashMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Parse and validate the text of a `rev.toml`.
+    fn parse(text: &str) -> Result<Settings, String> {
+        let settings: Settings = toml::from_str(text).map_err(|e| e.to_string())?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("max-bus-connections", self.max_bus_connections as u64),
+            ("max-log-size-mb", self.max_log_size_mb),
+            ("max-log-files", u64::from(self.max_log_files)),
+            ("activation-timeout-sec", self.activation_timeout_sec),
+        ];
+        if let Some((key, _)) = positive.iter().find(|(_, v)| *v == 0) {
+            return Err(format!("{} must be at least 1", key));
+        }
+        if !self.console_shell.starts_with('/') {
+            return Err(format!(
+                "console-shell '{}' is not an absolute path",
+                self.console_shell
+            ));
+        }
+        for (key, value) in [("console-path", &self.console_path), ("path", &self.path)] {
+            if value.is_empty() || value.contains('\0') {
+                return Err(format!("{} must be a non-empty search path", key));
+            }
+        }
+        if let Some(bad) = self
+            .env
+            .iter()
+            .find(|(k, v)| k.is_empty() || k.contains(['=', '\0']) || v.contains('\0'))
+        {
+            return Err(format!("env: invalid variable '{}'", bad.0));
+        }
+        Ok(())
+    }
+}
+
+/// Where `rev.toml` is looked for, highest precedence first.
+fn paths() -> [PathBuf; 2] {
+    if cfg!(debug_assertions) {
+        [
+            PathBuf::from("./Construct/Config/rev.toml"),
+            PathBuf::from("./Core/Config/rev.toml"),
+        ]
+    } else {
+        [
+            PathBuf::from("/Construct/Config/rev.toml"),
+            PathBuf::from("/Core/Config/rev.toml"),
+        ]
+    }
+}
+
+static CURRENT: Lazy<RwLock<Arc<Settings>>> = Lazy::new(|| RwLock::new(Arc::default()));
+
+/// The settings in force. Not for a forked child: read them before the fork.
+pub fn get() -> Arc<Settings> {
+    CURRENT.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Read `rev.toml` again and put it in force. Without a file the defaults
+/// apply. On an error the current settings are kept and the error returned.
+pub fn reload() -> Result<(), String> {
+    reload_from(&paths())
+}
+
+/// Put the first of `paths` that exists in force, or the defaults.
+fn reload_from(paths: &[PathBuf]) -> Result<(), String> {
+    let settings = match paths.iter().find(|p| p.exists()) {
+        None => Settings::default(),
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| Settings::parse(&text))
+            .map_err(|e| format!("{}: {}", path.display(), e))?,
+    };
+    let changed = {
+        let mut current = CURRENT.write().unwrap_or_else(|e| e.into_inner());
+        let changed = **current != settings;
+        *current = Arc::new(settings);
+        changed
+    };
+    // Logging reads the settings, so only once the write lock is released.
+    if changed {
+        crate::logger::write_log("rev", "Settings changed (rev.toml)");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_parsed_and_validated() {
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+
+        let settings = Settings::parse(
+            "max-log-files = 2\ntimeout-stop = 30\n[env]\nLANG = \"en_US.UTF-8\"\n",
+        )
+        .unwrap();
+        assert_eq!((settings.max_log_files, settings.timeout_stop), (2, 30));
+        assert_eq!(settings.max_bus_connections, 1024);
+        assert_eq!(settings.env["LANG"], "en_US.UTF-8");
+
+        assert!(Settings::parse("max-bus-connections = 0").is_err());
+        assert!(Settings::parse("console-shell = \"brush\"").is_err());
+        assert!(Settings::parse("[env]\n\"A=B\" = \"c\"").is_err());
+        // A misspelt key is reported, not ignored.
+        assert!(Settings::parse("max-log-file = 3").is_err());
+    }
+
+    #[test]
+    fn reload_puts_a_changed_file_in_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let construct = dir.path().join("construct.toml");
+        let core = dir.path().join("core.toml");
+        std::fs::write(&core, "console-path = \"/Core/Bin:/Core/Tools\"\n").unwrap();
+        let paths = [construct.clone(), core];
+
+        // A change is logged after the lock is released; this would hang.
+        reload_from(&paths).unwrap();
+        assert_eq!(get().console_path, "/Core/Bin:/Core/Tools");
+
+        // The first file found wins, and a bad one keeps what is in force.
+        std::fs::write(&construct, "console-path = \"\"\n").unwrap();
+        assert!(reload_from(&paths).is_err());
+        assert_eq!(get().console_path, "/Core/Bin:/Core/Tools");
+
+        reload_from(&[]).unwrap();
+        assert_eq!(*get(), Settings::default());
+    }
+}